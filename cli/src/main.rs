@@ -0,0 +1,429 @@
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+use lib::audio_manager::{DEFAULT_MAX_VOICES, MAX_VOICES_LIMIT};
+use lib::layout::{Layout, Spatial};
+use lib::pack::{ConversionReport, MechvibesExport, Pack, SampleSelection};
+use lib::render::{RenderSettings, read_session, render_to_wav};
+use lib::settings::Settings;
+use lib::validate::{Severity, validate_pack};
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+/// Manage and render WhisperKeys sound packs
+#[derive(Parser)]
+#[command(name = "whisperkeys", version)]
+struct Cli {
+    /// Folder the packs are installed in, the one from the app's settings by
+    /// default
+    #[arg(long, global = true)]
+    packs_dir: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the installed packs
+    List,
+    /// Create a pack from the config template
+    New {
+        /// Name of the pack folder to create in the packs folder
+        name: String,
+    },
+    /// Convert a Mechvibes pack folder into a new pack in the packs folder
+    Convert {
+        dir: PathBuf,
+        /// Convert every Mechvibes pack inside `dir` instead
+        #[arg(long)]
+        library: bool,
+    },
+    /// Check a .zip or .whisperpack archive and install it into the packs folder
+    Install { archive: PathBuf },
+    /// Pack an installed pack folder into a .whisperpack archive
+    Export {
+        /// Name of an installed pack, or path to a pack folder
+        pack: String,
+        /// Archive to write, `<pack>.whisperpack` by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Write an installed pack as a new Mechvibes pack folder
+    ExportMechvibes {
+        /// Name of an installed pack, or path to a pack folder
+        pack: String,
+        /// Folder to create the Mechvibes pack in
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+    },
+    /// Report every problem in a pack, exits with an error if it doesn't load
+    Validate {
+        /// Name of an installed pack, or path to a pack folder
+        pack: String,
+        /// Fail on warnings too
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Show a pack's details
+    Info {
+        /// Name of an installed pack, or path to a pack folder
+        pack: String,
+    },
+    /// List the audio output devices, to set `output_device` in the settings
+    Devices,
+    /// Render a typing session recorded from key_listener's output to a WAV file
+    Render {
+        /// Name of an installed pack, or path to a pack folder
+        pack: String,
+        /// Recorded session, e.g. `key_listener > session.jsonl`
+        session: PathBuf,
+        /// WAV file to write
+        #[arg(short, long, default_value = "session.wav")]
+        output: PathBuf,
+        /// Seed for the pitch, volume and sample variation
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Volume in percent, the pack's default volume otherwise
+        #[arg(long)]
+        volume: Option<u32>,
+        #[arg(long, default_value_t = 48_000)]
+        sample_rate: u32,
+        /// Pan every key to its place on a board: ansi, iso, tkl or 60
+        #[arg(long)]
+        layout: Option<Layout>,
+        /// Most sounds playing at once, the saved voice limit otherwise
+        #[arg(long)]
+        max_voices: Option<usize>,
+    },
+    /// Control the running app through its control socket
+    #[cfg(unix)]
+    Ctl {
+        #[command(subcommand)]
+        command: CtlCommand,
+        /// Socket to connect to, `$XDG_RUNTIME_DIR/whisperkeys.sock` by default
+        #[arg(long)]
+        socket: Option<PathBuf>,
+        /// Print the resulting state as JSON, for scripts and status bars
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[cfg(unix)]
+#[derive(Subcommand)]
+enum CtlCommand {
+    /// Switch to an installed pack
+    Pack {
+        name: String,
+    },
+    /// Set the volume in percent
+    Volume {
+        #[arg(value_parser = clap::value_parser!(u32).range(1..=100))]
+        percent: u32,
+    },
+    Mute,
+    Unmute,
+    ToggleMute,
+    /// Print the current pack, volume and mute state
+    Status,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let saved = Settings::load().unwrap_or_default();
+    let packs_dir = match cli.packs_dir {
+        Some(dir) => dir,
+        None => saved.packs_dir()?,
+    };
+
+    match cli.command {
+        Command::List => {
+            let mut packs = lib::pack::list_installed(&packs_dir)?;
+            packs.sort_unstable_by_key(|pack| pack.to_lowercase());
+
+            for pack in packs {
+                println!("{pack}");
+            }
+        }
+        Command::New { name } => {
+            let path = lib::pack::create_pack(&packs_dir, &name)?;
+            println!("Created {}", path.display());
+        }
+        Command::Convert { dir, library } => {
+            if library {
+                convert_library(&dir, &packs_dir)?;
+            } else {
+                let report = lib::pack::from_mechvibes(&dir, &packs_dir)
+                    .with_context(|| format!("Failed to convert {}", dir.display()))?;
+
+                println!("Converted {} to {}", dir.display(), report.path.display());
+                print_conversion_warnings(&report, "");
+            }
+        }
+        Command::Install { archive } => {
+            let name = lib::archive::install(&archive, &packs_dir)?;
+            println!("Installed {name}");
+        }
+        Command::Export { pack, output } => {
+            let folder = pack_path(&packs_dir, &pack);
+            let output = match output {
+                Some(output) => output,
+                None => {
+                    let name = folder
+                        .file_name()
+                        .with_context(|| format!("'{pack}' is not a pack folder"))?;
+                    // not with_extension, it would replace anything after a dot in the name
+                    PathBuf::from(format!("{}.whisperpack", name.to_string_lossy()))
+                }
+            };
+
+            lib::archive::export(&folder, &output)?;
+            println!("Exported {pack} to {}", output.display());
+        }
+        Command::ExportMechvibes { pack, output } => {
+            let report = lib::pack::to_mechvibes(&pack_path(&packs_dir, &pack), &output)
+                .with_context(|| format!("Failed to export {pack}"))?;
+
+            println!("Exported {pack} to {}", report.path.display());
+            print_export_warnings(&report);
+        }
+        Command::Validate { pack, strict } => {
+            let diagnostics = validate_pack(&pack_path(&packs_dir, &pack))?;
+
+            for diagnostic in &diagnostics {
+                println!("{diagnostic}");
+            }
+
+            let errors = diagnostics
+                .iter()
+                .filter(|d| d.severity == Severity::Error)
+                .count();
+            let warnings = diagnostics.len() - errors;
+
+            if errors > 0 || (strict && warnings > 0) {
+                anyhow::bail!("{pack}: {errors} error(s), {warnings} warning(s)");
+            }
+            println!("{pack}: no errors, {warnings} warning(s)");
+        }
+        Command::Info { pack } => print_info(&load_pack(&packs_dir, &pack)?),
+        Command::Devices => {
+            for device in lib::audio_manager::output_devices()? {
+                println!("{device}");
+            }
+        }
+        Command::Render {
+            pack,
+            session,
+            output,
+            seed,
+            volume,
+            sample_rate,
+            layout,
+            max_voices,
+        } => {
+            let max_voices = max_voices
+                .or(saved.max_voices)
+                .unwrap_or(DEFAULT_MAX_VOICES);
+            if !(1..=MAX_VOICES_LIMIT).contains(&max_voices) {
+                anyhow::bail!(
+                    "--max-voices must be between 1 and {MAX_VOICES_LIMIT}, got {max_voices}"
+                );
+            }
+            let pack = load_pack(&packs_dir, &pack)?;
+
+            let file = File::open(&session)
+                .with_context(|| format!("Failed to open {}", session.display()))?;
+            let events = read_session(BufReader::new(file))
+                .with_context(|| format!("Invalid session in {}", session.display()))?;
+
+            let settings = RenderSettings {
+                seed,
+                volume,
+                sample_rate,
+                spatial: layout.map(|layout| Spatial {
+                    layout,
+                    ..Spatial::default()
+                }),
+                max_voices,
+                variation: saved.variation,
+            };
+            render_to_wav(pack, &events, &settings, &output)?;
+
+            println!("Rendered {} events to {}", events.len(), output.display());
+        }
+        #[cfg(unix)]
+        Command::Ctl {
+            command,
+            socket,
+            json,
+        } => ctl(command, socket, json)?,
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn ctl(command: CtlCommand, socket: Option<PathBuf>, json: bool) -> Result<()> {
+    use lib::control::{self, Command, Response};
+
+    let command = match command {
+        CtlCommand::Pack { name } => Command::SetPack(name),
+        CtlCommand::Volume { percent } => Command::SetVolume(percent),
+        CtlCommand::Mute => Command::SetMuted(true),
+        CtlCommand::Unmute => Command::SetMuted(false),
+        CtlCommand::ToggleMute => Command::ToggleMute,
+        CtlCommand::Status => Command::Status,
+    };
+    let socket = socket.unwrap_or_else(control::socket_path);
+
+    let state = match control::send(&socket, &command)? {
+        Response::Ok(state) => state,
+        Response::Error(e) => anyhow::bail!(e),
+    };
+
+    if json {
+        println!("{}", serde_json::to_string(&state)?);
+    } else {
+        println!(
+            "{}, {}%{}",
+            state.pack.as_deref().unwrap_or("No pack"),
+            state.volume,
+            if state.muted { ", muted" } else { "" }
+        );
+    }
+
+    Ok(())
+}
+
+fn convert_library(library: &Path, packs_dir: &Path) -> Result<()> {
+    let results = lib::pack::from_mechvibes_library(library, packs_dir)?;
+    if results.is_empty() {
+        anyhow::bail!("No Mechvibes packs found in {}", library.display());
+    }
+
+    let mut failed = 0;
+    for (source, result) in &results {
+        match result {
+            Ok(report) => {
+                println!("ok      {} -> {}", source.display(), report.pack_name);
+                print_conversion_warnings(report, "        ");
+            }
+            Err(e) => {
+                failed += 1;
+                println!("failed  {}: {e:#}", source.display());
+            }
+        }
+    }
+
+    println!(
+        "\nConverted {} of {} packs",
+        results.len() - failed,
+        results.len()
+    );
+    if failed > 0 {
+        anyhow::bail!("{failed} pack(s) failed to convert");
+    }
+
+    Ok(())
+}
+
+fn print_conversion_warnings(report: &ConversionReport, indent: &str) {
+    if !report.unmapped_codes.is_empty() {
+        println!(
+            "{indent}Keycodes without a matching key, played as unknown keys: {}",
+            report.unmapped_codes.join(", ")
+        );
+    }
+    if !report.mismatched_codes.is_empty() {
+        println!(
+            "{indent}Keycodes whose sound doesn't fit the pack's key_define_type, left out: {}",
+            report.mismatched_codes.join(", ")
+        );
+    }
+    if report.missing_fallback {
+        println!("{indent}The pack has no sound for unknown keys, they will be silent");
+    }
+}
+
+fn print_export_warnings(report: &MechvibesExport) {
+    if !report.unmapped_keys.is_empty() {
+        println!(
+            "Keys without a Mechvibes keycode, left out: {}",
+            report.unmapped_keys.join(", ")
+        );
+    }
+    if !report.region_keys.is_empty() {
+        println!(
+            "Keys playing part of a file, left out: {}",
+            report.region_keys.join(", ")
+        );
+    }
+    if !report.silent_keys.is_empty() {
+        println!(
+            "Keys without any sound, left out: {}",
+            report.silent_keys.join(", ")
+        );
+    }
+    if !report.trimmed_keys.is_empty() {
+        println!(
+            "Keys with several samples, only the first one is used: {}",
+            report.trimmed_keys.join(", ")
+        );
+    }
+    if report.dropped_release {
+        println!("Mechvibes doesn't play release sounds, they were left out");
+    }
+}
+
+fn print_info(pack: &Pack) {
+    let samples: usize = pack.keys.values().map(Vec::len).sum();
+    let selection = match pack.sample_selection {
+        SampleSelection::Random => "random",
+        SampleSelection::RoundRobin => "round robin",
+    };
+
+    println!("Name:             {}", pack.name);
+    if !pack.creator.is_empty() {
+        println!("Creator:          {}", pack.creator);
+    }
+    if !pack.source.is_empty() {
+        println!("Source:           {}", pack.source);
+    }
+    println!("Default volume:   {}%", pack.default_volume);
+    println!("Keys:             {} ({samples} samples)", pack.keys.len());
+    println!("Release sounds:   {} keys", pack.release_keys.len());
+    println!(
+        "Default release:  {}",
+        if pack.default_release.is_empty() {
+            "no"
+        } else {
+            "yes"
+        }
+    );
+    println!("Sample selection: {selection}");
+}
+
+/// Loads `pack` from the packs folder, or from its own folder when given a path.
+fn load_pack(packs_dir: &Path, pack: &str) -> Result<Pack> {
+    let path = pack_path(packs_dir, pack);
+    let name = path
+        .file_name()
+        .with_context(|| format!("'{pack}' is not a pack folder"))?;
+    let parent = path.parent().unwrap_or(Path::new("."));
+
+    Pack::load_from(parent, &name.to_string_lossy())
+}
+
+/// Folder of `pack`, which is either a name in the packs folder or a path.
+fn pack_path(packs_dir: &Path, pack: &str) -> PathBuf {
+    let path = Path::new(pack);
+
+    if path.is_absolute() || path.components().count() > 1 {
+        path.to_owned()
+    } else {
+        packs_dir.join(pack)
+    }
+}
@@ -0,0 +1,220 @@
+use anyhow::{Result, bail};
+use evdev::{Device, EventSummary, KeyCode};
+use protocol::KeyEventKind;
+use rdev::Key;
+use std::{sync::mpsc, thread, time::SystemTime};
+
+/// Reads key events from every keyboard in `/dev/input` and calls `callback`
+/// with the rdev key they correspond to, so the output matches the rdev
+/// backend. The scan code passed along is the Linux keycode.
+pub fn listen(mut callback: impl FnMut(KeyEventKind, Key, u32, SystemTime)) -> Result<()> {
+    let keyboards: Vec<(_, Device)> = evdev::enumerate()
+        .filter(|(_, device)| {
+            device
+                .supported_keys()
+                .is_some_and(|keys| keys.contains(KeyCode::KEY_A))
+        })
+        .collect();
+
+    if keyboards.is_empty() {
+        bail!(
+            "No readable keyboard found in /dev/input, \
+            make sure your user is allowed to read it (e.g. is in the `input` group)"
+        );
+    }
+
+    let (tx, rx) = mpsc::channel();
+
+    for (path, mut device) in keyboards {
+        let tx = tx.clone();
+
+        thread::spawn(move || {
+            loop {
+                let events = match device.fetch_events() {
+                    Ok(events) => events,
+                    Err(e) => {
+                        eprintln!("Stopped reading {}: {e}", path.display());
+                        return;
+                    }
+                };
+
+                for event in events {
+                    // value is 0 for release, 1 for press and 2 for auto-repeat
+                    let time = event.timestamp();
+                    let (kind, code) = match event.destructure() {
+                        EventSummary::Key(_, code, 0) => (KeyEventKind::Release, code),
+                        EventSummary::Key(_, code, 1) => (KeyEventKind::Press, code),
+                        _ => continue,
+                    };
+
+                    if tx
+                        .send((kind, key_from_evdev(code), code.0.into(), time))
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    drop(tx);
+
+    for (kind, key, scan_code, time) in rx {
+        callback(kind, key, scan_code, time);
+    }
+
+    bail!("Lost access to every keyboard")
+}
+
+#[allow(clippy::too_many_lines)]
+fn key_from_evdev(code: KeyCode) -> Key {
+    match code {
+        KeyCode::KEY_ESC => Key::Escape,
+        KeyCode::KEY_F1 => Key::F1,
+        KeyCode::KEY_F2 => Key::F2,
+        KeyCode::KEY_F3 => Key::F3,
+        KeyCode::KEY_F4 => Key::F4,
+        KeyCode::KEY_F5 => Key::F5,
+        KeyCode::KEY_F6 => Key::F6,
+        KeyCode::KEY_F7 => Key::F7,
+        KeyCode::KEY_F8 => Key::F8,
+        KeyCode::KEY_F9 => Key::F9,
+        KeyCode::KEY_F10 => Key::F10,
+        KeyCode::KEY_F11 => Key::F11,
+        KeyCode::KEY_F12 => Key::F12,
+        KeyCode::KEY_F13 => Key::F13,
+        KeyCode::KEY_F14 => Key::F14,
+        KeyCode::KEY_F15 => Key::F15,
+        KeyCode::KEY_F16 => Key::F16,
+        KeyCode::KEY_F17 => Key::F17,
+        KeyCode::KEY_F18 => Key::F18,
+        KeyCode::KEY_F19 => Key::F19,
+        KeyCode::KEY_F20 => Key::F20,
+        KeyCode::KEY_F21 => Key::F21,
+        KeyCode::KEY_F22 => Key::F22,
+        KeyCode::KEY_F23 => Key::F23,
+        KeyCode::KEY_F24 => Key::F24,
+
+        KeyCode::KEY_GRAVE => Key::BackQuote,
+
+        KeyCode::KEY_1 => Key::Num1,
+        KeyCode::KEY_2 => Key::Num2,
+        KeyCode::KEY_3 => Key::Num3,
+        KeyCode::KEY_4 => Key::Num4,
+        KeyCode::KEY_5 => Key::Num5,
+        KeyCode::KEY_6 => Key::Num6,
+        KeyCode::KEY_7 => Key::Num7,
+        KeyCode::KEY_8 => Key::Num8,
+        KeyCode::KEY_9 => Key::Num9,
+        KeyCode::KEY_0 => Key::Num0,
+
+        KeyCode::KEY_MINUS => Key::Minus,
+        KeyCode::KEY_EQUAL => Key::Equal,
+        KeyCode::KEY_BACKSPACE => Key::Backspace,
+
+        KeyCode::KEY_TAB => Key::Tab,
+        KeyCode::KEY_CAPSLOCK => Key::CapsLock,
+
+        KeyCode::KEY_A => Key::KeyA,
+        KeyCode::KEY_B => Key::KeyB,
+        KeyCode::KEY_C => Key::KeyC,
+        KeyCode::KEY_D => Key::KeyD,
+        KeyCode::KEY_E => Key::KeyE,
+        KeyCode::KEY_F => Key::KeyF,
+        KeyCode::KEY_G => Key::KeyG,
+        KeyCode::KEY_H => Key::KeyH,
+        KeyCode::KEY_I => Key::KeyI,
+        KeyCode::KEY_J => Key::KeyJ,
+        KeyCode::KEY_K => Key::KeyK,
+        KeyCode::KEY_L => Key::KeyL,
+        KeyCode::KEY_M => Key::KeyM,
+        KeyCode::KEY_N => Key::KeyN,
+        KeyCode::KEY_O => Key::KeyO,
+        KeyCode::KEY_P => Key::KeyP,
+        KeyCode::KEY_Q => Key::KeyQ,
+        KeyCode::KEY_R => Key::KeyR,
+        KeyCode::KEY_S => Key::KeyS,
+        KeyCode::KEY_T => Key::KeyT,
+        KeyCode::KEY_U => Key::KeyU,
+        KeyCode::KEY_V => Key::KeyV,
+        KeyCode::KEY_W => Key::KeyW,
+        KeyCode::KEY_X => Key::KeyX,
+        KeyCode::KEY_Y => Key::KeyY,
+        KeyCode::KEY_Z => Key::KeyZ,
+
+        KeyCode::KEY_LEFTBRACE => Key::LeftBracket,
+        KeyCode::KEY_RIGHTBRACE => Key::RightBracket,
+        KeyCode::KEY_BACKSLASH => Key::BackSlash,
+        KeyCode::KEY_102ND => Key::IntlBackslash,
+        KeyCode::KEY_RO => Key::IntlRo,
+        KeyCode::KEY_YEN => Key::IntlYen,
+        KeyCode::KEY_KATAKANAHIRAGANA => Key::KanaMode,
+
+        KeyCode::KEY_SEMICOLON => Key::SemiColon,
+        KeyCode::KEY_APOSTROPHE => Key::Quote,
+        KeyCode::KEY_ENTER => Key::Return,
+
+        KeyCode::KEY_COMMA => Key::Comma,
+        KeyCode::KEY_DOT => Key::Dot,
+        KeyCode::KEY_SLASH => Key::Slash,
+
+        KeyCode::KEY_SPACE => Key::Space,
+
+        KeyCode::KEY_SYSRQ => Key::PrintScreen,
+        KeyCode::KEY_SCROLLLOCK => Key::ScrollLock,
+        KeyCode::KEY_PAUSE => Key::Pause,
+
+        KeyCode::KEY_INSERT => Key::Insert,
+        KeyCode::KEY_DELETE => Key::Delete,
+        KeyCode::KEY_HOME => Key::Home,
+        KeyCode::KEY_END => Key::End,
+        KeyCode::KEY_PAGEUP => Key::PageUp,
+        KeyCode::KEY_PAGEDOWN => Key::PageDown,
+
+        KeyCode::KEY_UP => Key::UpArrow,
+        KeyCode::KEY_LEFT => Key::LeftArrow,
+        KeyCode::KEY_RIGHT => Key::RightArrow,
+        KeyCode::KEY_DOWN => Key::DownArrow,
+
+        KeyCode::KEY_LEFTSHIFT => Key::ShiftLeft,
+        KeyCode::KEY_RIGHTSHIFT => Key::ShiftRight,
+        KeyCode::KEY_LEFTCTRL => Key::ControlLeft,
+        KeyCode::KEY_RIGHTCTRL => Key::ControlRight,
+        KeyCode::KEY_LEFTALT => Key::Alt,
+        KeyCode::KEY_RIGHTALT => Key::AltGr,
+        KeyCode::KEY_LEFTMETA => Key::MetaLeft,
+        KeyCode::KEY_RIGHTMETA => Key::MetaRight,
+
+        KeyCode::KEY_NUMLOCK => Key::NumLock,
+        KeyCode::KEY_KPSLASH => Key::KpDivide,
+        KeyCode::KEY_KPASTERISK => Key::KpMultiply,
+        KeyCode::KEY_KPMINUS => Key::KpMinus,
+        KeyCode::KEY_KPPLUS => Key::KpPlus,
+        KeyCode::KEY_KPENTER => Key::KpReturn,
+        KeyCode::KEY_KPDOT => Key::KpDelete,
+        KeyCode::KEY_KPEQUAL => Key::KpEqual,
+        KeyCode::KEY_KPCOMMA => Key::KpComma,
+
+        KeyCode::KEY_KP1 => Key::Kp1,
+        KeyCode::KEY_KP2 => Key::Kp2,
+        KeyCode::KEY_KP3 => Key::Kp3,
+        KeyCode::KEY_KP4 => Key::Kp4,
+        KeyCode::KEY_KP5 => Key::Kp5,
+        KeyCode::KEY_KP6 => Key::Kp6,
+        KeyCode::KEY_KP7 => Key::Kp7,
+        KeyCode::KEY_KP8 => Key::Kp8,
+        KeyCode::KEY_KP9 => Key::Kp9,
+        KeyCode::KEY_KP0 => Key::Kp0,
+
+        KeyCode::KEY_MUTE => Key::VolumeMute,
+        KeyCode::KEY_VOLUMEDOWN => Key::VolumeDown,
+        KeyCode::KEY_VOLUMEUP => Key::VolumeUp,
+        KeyCode::KEY_COMPOSE => Key::Apps,
+        KeyCode::KEY_SLEEP => Key::Sleep,
+
+        KeyCode::KEY_FN => Key::Function,
+
+        KeyCode(code) => Key::Unknown(code.into()),
+    }
+}
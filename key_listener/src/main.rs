@@ -1,33 +1,102 @@
-use rdev::{EventType, Key, ListenError};
-use std::io::{Write, stdout};
+use anyhow::{Result, bail};
+use protocol::{KeyEvent, KeyEventKind, Message, ModifierTracker};
+use rdev::{EventType, Key};
+use std::{
+    collections::HashSet,
+    io::{Write, stdout},
+    time::SystemTime,
+};
 
-fn main() -> Result<(), ListenError> {
-    let mut last_pressed_key: Option<Key> = None;
-    let mut last_event: Option<EventType> = None;
+#[cfg(target_os = "linux")]
+mod evdev_backend;
 
-    rdev::listen(move |event| {
-        if let EventType::KeyPress(key) = event.event_type {
-            let is_same_key = last_pressed_key == Some(key);
-            let last_event_is_keypress = matches!(last_event, Some(EventType::KeyPress(_)));
+const USAGE: &str = "Usage: key_listener [--backend rdev|evdev]";
 
-            if is_same_key && last_event_is_keypress {
-                return;
-            }
+/// Where key events are read from.
+enum Backend {
+    /// Global hook through rdev, works on macOS, Windows and X11.
+    Rdev,
+    /// Reads `/dev/input/event*` directly, for Wayland sessions on Linux.
+    #[cfg(target_os = "linux")]
+    Evdev,
+}
+
+fn parse_args() -> Result<Backend> {
+    let mut args = std::env::args().skip(1);
+    let mut backend = Backend::Rdev;
+
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "--backend" => args.next(),
+            _ => arg.strip_prefix("--backend=").map(str::to_owned),
+        };
+
+        backend = match value.as_deref() {
+            Some("rdev") => Backend::Rdev,
+            #[cfg(target_os = "linux")]
+            Some("evdev") => Backend::Evdev,
+            Some(other) => bail!("Unsupported backend '{other}'\n{USAGE}"),
+            None => bail!("Unexpected argument '{arg}'\n{USAGE}"),
+        };
+    }
 
-            let mut stdout = stdout();
-            if writeln!(stdout, "{:?}", key).is_err() {
-                return;
-            }
+    Ok(backend)
+}
+
+/// Writes key events to stdout, dropping the OS auto-repeat presses.
+#[derive(Default)]
+struct KeyOutput {
+    held_keys: HashSet<Key>,
+    modifiers: ModifierTracker,
+}
 
-            if stdout.flush().is_err() {
-                return;
-            }
+impl KeyOutput {
+    fn key_event(&mut self, kind: KeyEventKind, key: Key, scan_code: u32, time: SystemTime) {
+        let is_new = match kind {
+            KeyEventKind::Press => self.held_keys.insert(key),
+            KeyEventKind::Release => self.held_keys.remove(&key),
+        };
 
-            last_pressed_key = Some(key);
+        if !is_new {
+            return;
         }
 
-        last_event = Some(event.event_type);
-    })?;
+        self.modifiers.update(key, kind);
+
+        let event = KeyEvent::new(kind, key, scan_code, time, self.modifiers.modifiers());
+        write_message(&Message::Key(event));
+    }
+}
+
+fn write_message(message: &Message) {
+    let mut stdout = stdout();
+    if writeln!(stdout, "{}", message.to_line()).is_err() {
+        return;
+    }
+
+    let _ = stdout.flush();
+}
+
+fn main() -> Result<()> {
+    let backend = parse_args()?;
+    let mut output = KeyOutput::default();
+
+    write_message(&Message::hello());
+
+    match backend {
+        Backend::Rdev => rdev::listen(move |event| {
+            let (kind, key) = match event.event_type {
+                EventType::KeyPress(key) => (KeyEventKind::Press, key),
+                EventType::KeyRelease(key) => (KeyEventKind::Release, key),
+                _ => return,
+            };
 
-    Ok(())
+            output.key_event(kind, key, event.position_code, event.time);
+        })
+        .map_err(|e| anyhow::anyhow!("Failed to listen to keyboard events: {e:?}")),
+        #[cfg(target_os = "linux")]
+        Backend::Evdev => evdev_backend::listen(|kind, key, scan_code, time| {
+            output.key_event(kind, key, scan_code, time);
+        }),
+    }
 }
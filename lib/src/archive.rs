@@ -0,0 +1,281 @@
+//! Packs shared as a single `.zip` or `.whisperpack` file. Archives can be
+//! loaded as they are, or installed into the packs folder.
+//!
+//! The config is either at the root of the archive or in a single top-level
+//! folder, which is what zipping a pack folder produces.
+
+use anyhow::{Context, Result, bail};
+use std::{
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+use tempfile::TempDir;
+use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
+
+use crate::validate::{Severity, validate_pack};
+
+/// Extensions of the files read as pack archives.
+pub const ARCHIVE_EXTENSIONS: &[&str] = &["whisperpack", "zip"];
+
+/// Largest file read out of an archive, far above any key sound.
+const MAX_ENTRY_SIZE: u64 = 64 * 1024 * 1024;
+/// Largest amount of data read out of a single archive.
+const MAX_TOTAL_SIZE: u64 = 512 * 1024 * 1024;
+
+pub fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ARCHIVE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// An opened pack archive.
+pub(crate) struct PackArchive {
+    zip: ZipArchive<File>,
+    /// Folder the config is in, empty or ending with `/`.
+    root: String,
+    /// Bytes read out of the archive so far.
+    unpacked: u64,
+}
+
+impl PackArchive {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let zip = ZipArchive::new(file)
+            .with_context(|| format!("{} is not a valid archive", path.display()))?;
+
+        let root = zip
+            .file_names()
+            .filter_map(|name| {
+                name.strip_suffix("config.json5")
+                    .or_else(|| name.strip_suffix("config.json"))
+            })
+            .filter(|root| {
+                root.is_empty() || (root.ends_with('/') && root.matches('/').count() == 1)
+            })
+            .min_by_key(|root| root.len())
+            .with_context(|| format!("No config file found in {}", path.display()))?
+            .to_owned();
+
+        Ok(Self {
+            zip,
+            root,
+            unpacked: 0,
+        })
+    }
+
+    /// Name of the folder the pack is in inside the archive, if any.
+    pub fn folder_name(&self) -> Option<&str> {
+        self.root.strip_suffix('/')
+    }
+
+    /// Name of the pack in the archive at `path`: its folder in the archive, or
+    /// the archive's file name.
+    fn pack_name(&self, path: &Path) -> Result<String> {
+        match self.folder_name() {
+            Some(folder) => Ok(folder.to_owned()),
+            None => Ok(path
+                .file_stem()
+                .with_context(|| format!("{} has no file name", path.display()))?
+                .to_string_lossy()
+                .into_owned()),
+        }
+    }
+
+    pub fn read_config(&mut self) -> Result<String> {
+        let bytes = self
+            .read("config.json5")
+            .or_else(|_| self.read("config.json"))?;
+
+        String::from_utf8(bytes).context("The config file is not valid UTF-8")
+    }
+
+    /// Reads `file`, relative to the pack's folder in the archive.
+    pub fn read(&mut self, file: &str) -> Result<Vec<u8>> {
+        let name = format!("{}{}", self.root, file.trim_start_matches("./"));
+        let mut entry = self
+            .zip
+            .by_name(&name)
+            .with_context(|| format!("'{file}' is not in the archive"))?;
+
+        let mut bytes = Vec::new();
+        copy_limited(&mut entry, &mut bytes, &mut self.unpacked)
+            .with_context(|| format!("Failed to read '{file}' from the archive"))?;
+
+        Ok(bytes)
+    }
+
+    /// Unpacks the pack's folder into `dest`.
+    fn extract(&mut self, dest: &Path) -> Result<()> {
+        for index in 0..self.zip.len() {
+            let mut entry = self.zip.by_index(index)?;
+
+            let Some(path) = entry.enclosed_name() else {
+                bail!("'{}' points outside of the archive", entry.name());
+            };
+            // skips entries outside of the pack's folder
+            let Ok(relative) = path.strip_prefix(&self.root) else {
+                continue;
+            };
+            let target = dest.join(relative);
+
+            if entry.is_dir() {
+                fs::create_dir_all(&target)?;
+                continue;
+            }
+
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut file = File::create(&target)
+                .with_context(|| format!("Failed to create {}", target.display()))?;
+            copy_limited(&mut entry, &mut file, &mut self.unpacked)
+                .with_context(|| format!("Failed to extract {}", relative.display()))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Copies `entry` into `out`, failing once it or everything read from its
+/// archive gets too large. `unpacked` counts the bytes read so far.
+fn copy_limited(entry: impl Read, out: &mut impl Write, unpacked: &mut u64) -> Result<()> {
+    let limit = MAX_ENTRY_SIZE.min(MAX_TOTAL_SIZE - *unpacked);
+    let copied = io::copy(&mut entry.take(limit + 1), out)?;
+
+    if copied > MAX_ENTRY_SIZE {
+        bail!(
+            "The file is larger than {} MB",
+            MAX_ENTRY_SIZE / 1024 / 1024
+        );
+    }
+    if copied > limit {
+        bail!(
+            "The archive holds more than {} MB",
+            MAX_TOTAL_SIZE / 1024 / 1024
+        );
+    }
+
+    *unpacked += copied;
+    Ok(())
+}
+
+/// Unpacks the pack in the archive at `path` into a temporary folder named
+/// like the pack, for the tools that only work on folders. The folder is
+/// deleted when the returned [`TempDir`] is dropped.
+pub(crate) fn unpack_to_temp(path: &Path) -> Result<(TempDir, PathBuf)> {
+    let mut pack = PackArchive::open(path)?;
+    let name = pack.pack_name(path)?;
+
+    let temp = tempfile::tempdir().context("Failed to create a temporary folder")?;
+    let folder = temp.path().join(name);
+    pack.extract(&folder)
+        .with_context(|| format!("Failed to unpack {}", path.display()))?;
+
+    Ok((temp, folder))
+}
+
+/// Checks the pack in `archive` and unpacks it into `packs_dir`. The folder is
+/// named after the one in the archive, or the archive's file name. Returns the
+/// name of the installed pack.
+pub fn install(archive: &Path, packs_dir: &Path) -> Result<String> {
+    let mut pack = PackArchive::open(archive)?;
+    let name = pack.pack_name(archive)?;
+
+    let destination = packs_dir.join(&name);
+    if destination.exists() {
+        bail!("A pack named '{name}' is already installed");
+    }
+
+    // unpack in a hidden folder next to the destination first, so a broken
+    // pack never shows up in the list and the move can't cross filesystems
+    let staging = tempfile::Builder::new()
+        .prefix(".installing-")
+        .tempdir_in(packs_dir)
+        .with_context(|| format!("Failed to create a folder in {}", packs_dir.display()))?;
+
+    pack.extract(staging.path())
+        .and_then(|()| check(staging.path()))
+        .and_then(|()| {
+            fs::rename(staging.path(), &destination)
+                .with_context(|| format!("Failed to install to {}", destination.display()))
+        })
+        .with_context(|| format!("Failed to install {}", archive.display()))?;
+
+    Ok(name)
+}
+
+/// Checks the pack in `folder` and writes it to `archive`, inside a folder
+/// named like the pack.
+pub fn export(folder: &Path, archive: &Path) -> Result<()> {
+    check(folder)?;
+
+    let name = folder
+        .file_name()
+        .with_context(|| format!("{} is not a pack folder", folder.display()))?
+        .to_string_lossy()
+        .into_owned();
+
+    let mut files = Vec::new();
+    collect_files(folder, Path::new(""), &mut files)?;
+    files.sort();
+
+    let file =
+        File::create(archive).with_context(|| format!("Failed to create {}", archive.display()))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default();
+
+    for relative in files {
+        // zip paths always use forward slashes
+        let entry_name = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        zip.start_file(format!("{name}/{entry_name}"), options)?;
+        let mut source = File::open(folder.join(&relative))?;
+        io::copy(&mut source, &mut zip)
+            .with_context(|| format!("Failed to add {} to the archive", relative.display()))?;
+    }
+
+    zip.finish()
+        .with_context(|| format!("Failed to write {}", archive.display()))?;
+
+    Ok(())
+}
+
+/// Fails with every error found in the pack, warnings don't stop it.
+fn check(folder: &Path) -> Result<()> {
+    let errors = validate_pack(folder)?
+        .into_iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .map(|diagnostic| diagnostic.message)
+        .collect::<Vec<_>>();
+
+    if !errors.is_empty() {
+        bail!("The pack has errors:\n{}", errors.join("\n"));
+    }
+
+    Ok(())
+}
+
+fn collect_files(root: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let dir = root.join(relative);
+    let entries =
+        fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+
+    for entry in entries {
+        let entry = entry?;
+        let path = relative.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            collect_files(root, &path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
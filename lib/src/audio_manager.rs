@@ -1,111 +1,735 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use cpal::traits::{DeviceTrait, HostTrait};
 use fastrand::Rng;
-use kira::{AudioManagerSettings, Decibels, DefaultBackend, Semitones};
+use kira::{
+    AudioManagerSettings, Decibels, Panning, PlaySoundError, Semitones, Tween,
+    backend::cpal::CpalBackendSettings,
+    sound::{
+        PlaybackState,
+        static_sound::{StaticSoundData, StaticSoundHandle},
+    },
+    track::MainTrackBuilder,
+};
 use std::{
-    sync::mpsc::{self, Receiver, Sender},
+    collections::{HashMap, VecDeque},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
     thread::{self},
+    time::{Duration, Instant},
 };
 
-use crate::pack::Pack;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    keys,
+    layout::{PanMap, Spatial},
+    pack::{Pack, SampleSelection, Variation},
+};
 
 #[derive(Debug)]
 pub enum AudioMessage {
     SetVolume(u32),
     ToggleMute,
+    SetMuted(bool),
     SetPack(Pack),
+    /// Swaps in a new version of the current pack, keeping the volume. Ignored
+    /// if another pack was set since it was loaded.
+    ReloadPack(Pack),
+    /// Pans every key to its place on the board, `None` or a disabled one
+    /// plays them all in the middle.
+    SetSpatial(Option<Spatial>),
+    /// Overrides the pack's pitch and volume variation, `None` goes back to
+    /// the pack's.
+    SetVariation(Option<Variation>),
+    /// Most sounds playing at once, the oldest ones are faded out to make
+    /// room for new ones.
+    SetMaxVoices(usize),
+    /// Plays through the output device with the given name, the system's
+    /// default one when `None` or when there is no such device.
+    SetOutputDevice(Option<String>),
     KeyPressed(String),
+    KeyReleased(String),
+    /// Replies with the current [`AudioState`] on the given channel.
+    QueryState(Sender<AudioState>),
+    /// Sends [`AudioState::output_error`] on the given channel every time it
+    /// changes, until the receiver is dropped.
+    WatchOutput(Sender<Option<String>>),
     Shutdown,
 }
 
+/// What the audio manager is currently playing with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AudioState {
+    /// Name of the loaded pack, `None` until one is set.
+    pub pack: Option<String>,
+    pub volume: u32,
+    pub muted: bool,
+    /// Variation in effect, the user's or the pack's.
+    #[serde(default)]
+    pub variation: Variation,
+    /// Output device asked for, `None` for the system's default one.
+    #[serde(default)]
+    pub output_device: Option<String>,
+    /// Why the sounds don't play through the device asked for, if they don't.
+    #[serde(default)]
+    pub output_error: Option<String>,
+}
+
 #[derive(Clone)]
 pub struct AudioManager {
     sender: Sender<AudioMessage>,
 }
 
+/// Voices playing at once unless the user sets another limit.
+pub const DEFAULT_MAX_VOICES: usize = 32;
+
+/// Highest voice limit that can be set.
+pub const MAX_VOICES_LIMIT: usize = 128;
+
+/// Sounds kira can hold at once. Sounds cut off by the voice limit still take
+/// a slot while they fade out, hence the room above the limit.
+const SOUND_CAPACITY: usize = MAX_VOICES_LIMIT * 2;
+
+/// Fade out of the sounds cut off by a newer one, short enough to go
+/// unnoticed but long enough not to click.
+pub(crate) const CUT_FADE: Duration = Duration::from_millis(15);
+
+/// How often the output is checked for a lost or changed device.
+const DEVICE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Where the audio manager sends the sounds it decides to play.
+pub trait SoundOutput: Send + 'static {
+    fn play(&mut self, voice: Voice) -> Result<()>;
+
+    /// Limits how many voices play at once. Outputs that don't play anything
+    /// live can ignore it.
+    fn set_max_voices(&mut self, _max_voices: usize) {}
+
+    /// Switches to the output device named `device`, the system's default one
+    /// when `None`. Outputs that don't play anything live can ignore it.
+    fn set_device(&mut self, _device: Option<&str>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called every [`DEVICE_CHECK_INTERVAL`] to reopen the output when its
+    /// device is gone or another one should be used. The error says why the
+    /// sounds don't play through the device asked for, like
+    /// [`SoundOutput::set_device`]'s.
+    fn check_device(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Names of the audio output devices that can be passed to
+/// [`AudioMessage::SetOutputDevice`].
+pub fn output_devices() -> Result<Vec<String>> {
+    let devices = cpal::default_host()
+        .output_devices()
+        .context("Failed to list the audio output devices")?;
+
+    let mut names = devices
+        .filter_map(|device| device.name().ok())
+        .collect::<Vec<_>>();
+    names.dedup();
+
+    Ok(names)
+}
+
+fn find_device(name: &str) -> Option<cpal::Device> {
+    cpal::default_host()
+        .output_devices()
+        .ok()?
+        .find(|device| device.name().is_ok_and(|device_name| device_name == name))
+}
+
+fn default_device_name() -> Option<String> {
+    cpal::default_host().default_output_device()?.name().ok()
+}
+
+fn open_device(device: Option<cpal::Device>) -> Result<kira::AudioManager> {
+    let settings = AudioManagerSettings {
+        backend_settings: CpalBackendSettings {
+            device,
+            ..CpalBackendSettings::default()
+        },
+        main_track_builder: MainTrackBuilder::new().sound_capacity(SOUND_CAPACITY),
+        ..AudioManagerSettings::default()
+    };
+
+    kira::AudioManager::new(settings).context("Failed to open the audio output device")
+}
+
+/// Voices an output is playing, oldest first, to cut off the ones a new voice
+/// replaces. Shared with the offline renderer so it cuts off the same ones.
+pub(crate) struct PlayingVoices<H> {
+    voices: VecDeque<(H, Option<String>)>,
+    max_voices: usize,
+}
+
+impl<H> PlayingVoices<H> {
+    pub fn new(max_voices: usize) -> Self {
+        Self {
+            voices: VecDeque::new(),
+            max_voices: max_voices.clamp(1, MAX_VOICES_LIMIT),
+        }
+    }
+
+    pub fn set_max_voices(&mut self, max_voices: usize) {
+        self.max_voices = max_voices.clamp(1, MAX_VOICES_LIMIT);
+    }
+
+    /// Forgets the voices `playing` says are done.
+    pub fn retain(&mut self, mut playing: impl FnMut(&H) -> bool) {
+        self.voices.retain(|(handle, _)| playing(handle));
+    }
+
+    /// Makes room for a voice of `cut_group`, passing `stop` the playing
+    /// voices of the same group and then the oldest ones over the limit.
+    pub fn make_room(&mut self, cut_group: Option<&str>, mut stop: impl FnMut(&mut H)) {
+        if let Some(group) = cut_group {
+            self.voices.retain_mut(|(handle, playing_group)| {
+                let cut = playing_group.as_deref() == Some(group);
+                if cut {
+                    stop(handle);
+                }
+                !cut
+            });
+        }
+
+        while self.voices.len() >= self.max_voices {
+            let Some((mut oldest, _)) = self.voices.pop_front() else {
+                break;
+            };
+            stop(&mut oldest);
+        }
+    }
+
+    pub fn push(&mut self, handle: H, cut_group: Option<String>) {
+        self.voices.push_back((handle, cut_group));
+    }
+
+    pub fn clear(&mut self) {
+        self.voices.clear();
+    }
+}
+
+/// Plays through kira, keeping track of the playing sounds to cut them off
+/// when there are too many of them or a voice asks for it.
+pub struct KiraOutput {
+    /// `None` while no device could be opened, until a check opens one.
+    manager: Option<kira::AudioManager>,
+    playing: PlayingVoices<StaticSoundHandle>,
+    /// Device asked for, `None` for the system's default one.
+    device: Option<String>,
+    /// The device asked for wasn't found, the default one plays instead.
+    fell_back: bool,
+    /// Default device playing when none was asked for, to follow it when it
+    /// changes.
+    default_device: Option<String>,
+}
+
+impl KiraOutput {
+    /// Plays through the system's default output device.
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            manager: Some(open_device(None)?),
+            playing: PlayingVoices::new(DEFAULT_MAX_VOICES),
+            device: None,
+            fell_back: false,
+            default_device: default_device_name(),
+        })
+    }
+
+    /// Opens the device asked for again, or the default one when it isn't
+    /// there.
+    fn reopen(&mut self) -> Result<()> {
+        // drops the old device before opening one
+        self.manager = None;
+        self.playing.clear();
+
+        let found = self.device.as_deref().and_then(find_device);
+        self.fell_back = self.device.is_some() && found.is_none();
+        self.default_device = self.device.is_none().then(default_device_name).flatten();
+        self.manager = Some(open_device(found)?);
+
+        self.fallback_error()
+    }
+
+    fn fallback_error(&self) -> Result<()> {
+        if let (true, Some(name)) = (self.fell_back, &self.device) {
+            anyhow::bail!("Output device '{name}' not found, playing through the default one");
+        }
+        Ok(())
+    }
+}
+
+impl SoundOutput for KiraOutput {
+    fn play(&mut self, voice: Voice) -> Result<()> {
+        let fade = Tween {
+            duration: CUT_FADE,
+            ..Tween::default()
+        };
+
+        self.playing
+            .retain(|handle| handle.state() != PlaybackState::Stopped);
+        self.playing
+            .make_room(voice.cut_group.as_deref(), |handle| handle.stop(fade));
+
+        let sound_data = voice
+            .sound
+            .volume(voice.volume)
+            .playback_rate(voice.semitones)
+            .panning(voice.panning);
+
+        let Some(manager) = &mut self.manager else {
+            anyhow::bail!("No audio output device");
+        };
+
+        match manager.play(sound_data) {
+            Ok(handle) => {
+                self.playing.push(handle, voice.cut_group);
+                Ok(())
+            }
+            // every slot is taken by sounds still fading out, this one is
+            // skipped rather than cutting off another
+            Err(PlaySoundError::SoundLimitReached) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn set_max_voices(&mut self, max_voices: usize) {
+        self.playing.set_max_voices(max_voices);
+    }
+
+    /// Falls back to the default device when there is no device named
+    /// `device`, the error then says so. Keeps the output open, and the sounds
+    /// playing, when it already plays through `device`.
+    fn set_device(&mut self, device: Option<&str>) -> Result<()> {
+        if self.manager.is_some() && !self.fell_back && self.device.as_deref() == device {
+            return Ok(());
+        }
+
+        self.device = device.map(str::to_owned);
+        self.reopen()
+    }
+
+    /// Reopens the output when the stream failed, when the device asked for
+    /// came back after falling back to the default one, or when the default
+    /// device changed while playing through it.
+    fn check_device(&mut self) -> Result<()> {
+        let failed = match &mut self.manager {
+            Some(manager) => {
+                let mut failed = false;
+                while let Some(e) = manager.backend_mut().pop_error() {
+                    eprintln!("Audio output failed: {e}");
+                    failed = true;
+                }
+                failed
+            }
+            None => true,
+        };
+
+        let changed = match &self.device {
+            Some(device) => self.fell_back && find_device(device).is_some(),
+            None => default_device_name() != self.default_device,
+        };
+
+        if failed || changed {
+            self.reopen()
+        } else {
+            self.fallback_error()
+        }
+    }
+}
+
+/// Output that plays nothing and forwards every voice to a channel instead,
+/// to check what would have been played without an audio device.
+pub struct RecordingOutput {
+    sender: Sender<Voice>,
+}
+
+impl RecordingOutput {
+    pub fn new() -> (Self, Receiver<Voice>) {
+        let (sender, receiver) = mpsc::channel();
+        (Self { sender }, receiver)
+    }
+}
+
+impl SoundOutput for RecordingOutput {
+    fn play(&mut self, voice: Voice) -> Result<()> {
+        self.sender.send(voice)?;
+        Ok(())
+    }
+}
+
 struct AudioManagerActor {
     receiver: Receiver<AudioMessage>,
+    state: SoundState,
+    output: Box<dyn SoundOutput>,
+    output_device: Option<String>,
+    output_error: Option<String>,
+    /// Told about every change of `output_error`.
+    output_watchers: Vec<Sender<Option<String>>>,
+}
+
+/// Everything that decides which sound a key event plays and how. Shared with
+/// the offline renderer so both produce the same mix for the same seed.
+pub(crate) struct SoundState {
     muted: bool,
     volume: u32,
     cached_db: f32,
     pack: Option<Pack>,
-    manager: kira::AudioManager,
     rng: Rng,
+    spatial: Option<(Spatial, PanMap)>,
+    /// User's variation, used instead of the pack's.
+    variation: Option<Variation>,
+    press_samples: SampleCursor,
+    release_samples: SampleCursor,
 }
 
-impl AudioManagerActor {
-    pub fn new(rcv: Receiver<AudioMessage>) -> Result<Self> {
-        Ok(Self {
-            receiver: rcv,
+/// A sample picked for a key event, with its variation applied.
+#[derive(Debug)]
+pub struct Voice {
+    /// Pack entry the sample comes from, `"Unknown"` for the fallback and
+    /// empty for the pack's default release sound.
+    pub key: String,
+    pub sound: StaticSoundData,
+    pub volume: Decibels,
+    pub semitones: Semitones,
+    pub panning: Panning,
+    /// Set for keys whose new press cuts off their previous one, a playing
+    /// voice of the same group is faded out when this one starts.
+    pub cut_group: Option<String>,
+}
+
+/// Remembers the last sample played for each key so the next pick can avoid
+/// repeating it.
+#[derive(Default)]
+struct SampleCursor {
+    last_played: HashMap<String, usize>,
+}
+
+impl SampleCursor {
+    fn next(&mut self, rng: &mut Rng, selection: SampleSelection, key: &str, len: usize) -> usize {
+        let last = self.last_played.get(key).copied();
+
+        let index = match (selection, last) {
+            _ if len <= 1 => 0,
+            (SampleSelection::RoundRobin, Some(last)) => (last + 1) % len,
+            (SampleSelection::RoundRobin, None) => 0,
+            (SampleSelection::Random, Some(last)) => {
+                // pick among the other samples by skipping over the last one
+                let index = rng.usize(..len - 1);
+                if index >= last { index + 1 } else { index }
+            }
+            (SampleSelection::Random, None) => rng.usize(..len),
+        };
+
+        self.last_played.insert(key.to_owned(), index);
+        index
+    }
+}
+
+impl SoundState {
+    pub fn new(rng: Rng) -> Self {
+        Self {
             muted: false,
             volume: 50,
             pack: None,
-            manager: kira::AudioManager::<DefaultBackend>::new(AudioManagerSettings::default())?,
-            rng: Rng::new(),
+            rng,
+            spatial: None,
+            variation: None,
             cached_db: 20.0 * 0.5_f32.log10(),
-        })
+            press_samples: SampleCursor::default(),
+            release_samples: SampleCursor::default(),
+        }
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    pub fn update_volume(&mut self, volume: u32) {
+        self.volume = volume;
+
+        // dB = 20 * log_10(Amplitude)
+        self.cached_db = 20.0 * (volume as f32 * 0.01).log10();
+    }
+
+    pub fn set_pack(&mut self, pack: Pack) {
+        self.update_volume(pack.default_volume);
+        self.replace_pack(pack);
+    }
+
+    /// Swaps in a new version of the loaded pack, unless `pack` is another
+    /// one, so a late reload can't bring back a pack that was switched away
+    /// from.
+    pub fn reload_pack(&mut self, pack: Pack) {
+        if self
+            .pack
+            .as_ref()
+            .is_some_and(|current| current.name == pack.name)
+        {
+            self.replace_pack(pack);
+        }
+    }
+
+    fn replace_pack(&mut self, pack: Pack) {
+        self.press_samples = SampleCursor::default();
+        self.release_samples = SampleCursor::default();
+        self.pack = Some(pack);
+    }
+
+    pub fn set_spatial(&mut self, spatial: Option<Spatial>) {
+        self.spatial = spatial
+            .filter(|spatial| spatial.enabled)
+            .map(|spatial| (spatial, PanMap::new(spatial.layout)));
+    }
+
+    pub fn set_variation(&mut self, variation: Option<Variation>) {
+        self.variation = variation;
+    }
+
+    fn variation(&self) -> Variation {
+        self.variation
+            .or_else(|| self.pack.as_ref().map(|pack| pack.variation))
+            .unwrap_or_default()
+    }
+
+    pub fn key_pressed(&mut self, key: &str) -> Option<Voice> {
+        if self.muted {
+            return None;
+        }
+
+        let placement = self.placement(key);
+        let pack = self.pack.as_ref()?;
+
+        let pressed = key;
+        let (key, samples) = pack
+            .keys
+            .get_key_value(key)
+            .or_else(|| pack.keys.get_key_value(keys::UNKNOWN_KEY))?;
+
+        // "Unknown" in the list cuts every key that falls back to it
+        let cut_group = (pack.cut_previous.contains(pressed) || pack.cut_previous.contains(key))
+            .then(|| pressed.to_owned());
+
+        let index =
+            self.press_samples
+                .next(&mut self.rng, pack.sample_selection, key, samples.len());
+
+        let mut voice = self.voice(key.to_owned(), samples[index].clone(), placement);
+        voice.cut_group = cut_group;
+        Some(voice)
+    }
+
+    pub fn key_released(&mut self, key: &str) -> Option<Voice> {
+        if self.muted {
+            return None;
+        }
+
+        let placement = self.placement(key);
+        let pack = self.pack.as_ref()?;
+
+        let (key, samples) = match pack.release_keys.get_key_value(key) {
+            Some((key, samples)) => (key.as_str(), samples.as_slice()),
+            None if !pack.default_release.is_empty() => ("", pack.default_release.as_slice()),
+            None => return None,
+        };
+
+        let index =
+            self.release_samples
+                .next(&mut self.rng, pack.sample_selection, key, samples.len());
+
+        let voice = self.voice(key.to_owned(), samples[index].clone(), placement);
+        Some(voice)
+    }
+
+    pub fn state(&self) -> AudioState {
+        AudioState {
+            pack: self.pack.as_ref().map(|pack| pack.name.clone()),
+            volume: self.volume,
+            muted: self.muted,
+            variation: self.variation(),
+            output_device: None,
+            output_error: None,
+        }
+    }
+
+    /// Panning and volume change of the pressed `key` in the spatial mode,
+    /// centered and unchanged otherwise. Keys the layout doesn't have stay in
+    /// the middle.
+    fn placement(&self, key: &str) -> (Panning, f32) {
+        let Some((spatial, pan_map)) = &self.spatial else {
+            return (Panning::CENTER, 0.0);
+        };
+
+        let position = self
+            .pack
+            .as_ref()
+            .and_then(|pack| pack.pan.get(key).copied())
+            .or_else(|| pan_map.position(key))
+            .unwrap_or(0.0)
+            .clamp(-1.0, 1.0);
+
+        (
+            Panning(position * spatial.width),
+            -spatial.attenuation * position.abs(),
+        )
+    }
+
+    fn voice(&mut self, key: String, sound: StaticSoundData, placement: (Panning, f32)) -> Voice {
+        let (panning, attenuation) = placement;
+
+        let variation = self.variation();
+        let rng = &mut self.rng;
+
+        // values in [-pitch, pitch] and [-volume, volume]
+        let semitone_shift = variation.pitch * variation.distribution.sample(|| rng.f64());
+        let db_variation =
+            variation.volume * variation.distribution.sample(|| f64::from(rng.f32())) as f32;
+        let final_db = self.cached_db + db_variation + attenuation;
+
+        Voice {
+            key,
+            sound,
+            volume: Decibels(final_db),
+            semitones: Semitones(semitone_shift),
+            panning,
+            cut_group: None,
+        }
+    }
+}
+
+impl AudioManagerActor {
+    pub fn new(rcv: Receiver<AudioMessage>, output: Box<dyn SoundOutput>, rng: Rng) -> Self {
+        Self {
+            receiver: rcv,
+            state: SoundState::new(rng),
+            output,
+            output_device: None,
+            output_error: None,
+            output_watchers: Vec::new(),
+        }
     }
 
     fn start(&mut self) {
+        let mut next_check = Instant::now() + DEVICE_CHECK_INTERVAL;
+
         loop {
-            match self.receiver.recv() {
-                Ok(AudioMessage::ToggleMute) => self.muted = !self.muted,
-                Ok(AudioMessage::SetVolume(v)) => self.update_volume(v),
+            let timeout = next_check.saturating_duration_since(Instant::now());
+
+            let voice = match self.receiver.recv_timeout(timeout) {
+                Ok(AudioMessage::ToggleMute) => {
+                    self.state.set_muted(!self.state.muted);
+                    None
+                }
+                Ok(AudioMessage::SetMuted(muted)) => {
+                    self.state.set_muted(muted);
+                    None
+                }
+                Ok(AudioMessage::SetVolume(v)) => {
+                    self.state.update_volume(v);
+                    None
+                }
                 Ok(AudioMessage::SetPack(pack)) => {
-                    self.update_volume(pack.default_volume);
-                    self.pack = Some(pack);
+                    self.state.set_pack(pack);
+                    None
+                }
+                Ok(AudioMessage::ReloadPack(pack)) => {
+                    self.state.reload_pack(pack);
+                    None
+                }
+                Ok(AudioMessage::SetSpatial(spatial)) => {
+                    self.state.set_spatial(spatial);
+                    None
+                }
+                Ok(AudioMessage::SetVariation(variation)) => {
+                    self.state.set_variation(variation);
+                    None
+                }
+                Ok(AudioMessage::SetMaxVoices(max_voices)) => {
+                    self.output.set_max_voices(max_voices);
+                    None
+                }
+                Ok(AudioMessage::SetOutputDevice(device)) => {
+                    let result = self.output.set_device(device.as_deref());
+                    self.set_output_error(result.err().map(|e| format!("{e:#}")));
+                    self.output_device = device;
+                    None
                 }
-                Ok(AudioMessage::KeyPressed(key)) => {
-                    if !self.muted {
-                        self.handle_keypress(key);
+                Ok(AudioMessage::KeyPressed(key)) => self.state.key_pressed(&key),
+                Ok(AudioMessage::KeyReleased(key)) => self.state.key_released(&key),
+                Ok(AudioMessage::QueryState(reply)) => {
+                    let state = AudioState {
+                        output_device: self.output_device.clone(),
+                        output_error: self.output_error.clone(),
+                        ..self.state.state()
+                    };
+
+                    // the asker may have given up waiting, nothing to do then
+                    let _ = reply.send(state);
+                    None
+                }
+                Ok(AudioMessage::WatchOutput(watcher)) => {
+                    if watcher.send(self.output_error.clone()).is_ok() {
+                        self.output_watchers.push(watcher);
                     }
+                    None
                 }
                 Ok(AudioMessage::Shutdown) => break,
-                Err(_) => break,
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+
+            // a lost device is found by the next check, which tries to
+            // recover, not by every key press failing while typing
+            if let Some(voice) = voice
+                && let Err(e) = self.output.play(voice)
+                && self.output_error.is_none()
+            {
+                eprintln!("Failed to play sound: {e:#}");
+            }
+
+            if Instant::now() >= next_check {
+                let result = self.output.check_device();
+                self.set_output_error(result.err().map(|e| format!("{e:#}")));
+                next_check = Instant::now() + DEVICE_CHECK_INTERVAL;
             }
         }
     }
 
-    fn update_volume(&mut self, volume: u32) {
-        self.volume = volume;
-
-        // dB = 20 * log_10(Amplitude)
-        self.cached_db = 20.0 * (volume as f32 * 0.01).log10();
-    }
+    /// Reports `error` when it isn't the one already reported.
+    fn set_output_error(&mut self, error: Option<String>) {
+        if error == self.output_error {
+            return;
+        }
 
-    fn handle_keypress(&mut self, key: String) {
-        if let Some(pack) = &self.pack {
-            // generates value in [-0.25, 0.25]
-            let semitone_shift = self.rng.f64() * 0.5 - 0.25;
-            let db_variation = self.rng.f32() * 2.0 - 1.0; // random float in [-1.0, 1.0]
-            let final_db = self.cached_db + db_variation;
-
-            let sound_data = pack
-                .keys
-                .get(&key)
-                .or_else(|| pack.keys.get("Unknown"))
-                .map(|sound| {
-                    sound
-                        .volume(Decibels(final_db))
-                        .playback_rate(Semitones(semitone_shift))
-                });
-
-            if let Some(sound_data) = sound_data {
-                if let Err(e) = self.manager.play(sound_data) {
-                    eprintln!("Failed to play sound: {}", e);
-                }
-            }
+        match &error {
+            Some(error) => eprintln!("{error}"),
+            None => eprintln!("Audio output is back"),
         }
+        self.output_watchers
+            .retain(|watcher| watcher.send(error.clone()).is_ok());
+        self.output_error = error;
     }
 }
 
 impl AudioManager {
+    /// Plays through the system's default audio device.
     pub fn new() -> Result<Self> {
+        Ok(Self::with_output(KiraOutput::new()?, None))
+    }
+
+    /// Sends the sounds to `output`, with the pitch, volume and sample
+    /// variation seeded by `seed` when given.
+    pub fn with_output(output: impl SoundOutput, seed: Option<u64>) -> Self {
         let (tx, rx) = mpsc::channel::<AudioMessage>();
-        let mut actor = AudioManagerActor::new(rx)?;
+        let rng = seed.map_or_else(Rng::new, Rng::with_seed);
+        let mut actor = AudioManagerActor::new(rx, Box::new(output), rng);
 
         thread::spawn(move || actor.start());
 
-        Ok(Self { sender: tx })
+        Self { sender: tx }
     }
 
     pub fn shutdown(self) {
@@ -116,4 +740,13 @@ impl AudioManager {
         self.sender.send(msg)?;
         Ok(())
     }
+
+    /// Asks the audio thread for its current state, after every message sent
+    /// before this call has been handled.
+    pub fn state(&self) -> Result<AudioState> {
+        let (tx, rx) = mpsc::channel();
+        self.send(AudioMessage::QueryState(tx))?;
+
+        rx.recv().context("Audio manager stopped before replying")
+    }
 }
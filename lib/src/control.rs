@@ -0,0 +1,292 @@
+//! Unix domain socket for controlling a running WhisperKeys from scripts,
+//! global hotkeys or status bar widgets.
+//!
+//! Clients send one command per line and get one JSON line back for each:
+//!
+//! ```text
+//! > pack Cherry MX Blue
+//! < {"ok":{"pack":"Cherry MX Blue","volume":50,"muted":false}}
+//! > volume 120
+//! < {"error":"Volume must be between 1 and 100, got 120"}
+//! ```
+//!
+//! The commands are `pack <name>`, `volume <percent>`, `mute`, `unmute`,
+//! `toggle-mute` and `status`. Every successful command replies with the state
+//! after it was applied, so `status` only exists to ask without changing it.
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::{
+        fs::{DirBuilderExt, FileTypeExt, MetadataExt, PermissionsExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+};
+
+use crate::{
+    audio_manager::{AudioManager, AudioMessage, AudioState},
+    pack::Pack,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    SetPack(String),
+    SetVolume(u32),
+    SetMuted(bool),
+    ToggleMute,
+    Status,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self> {
+        let line = line.trim();
+        let (name, arg) = line.split_once(' ').unwrap_or((line, ""));
+        let arg = arg.trim();
+
+        let command = match (name, arg) {
+            ("pack", "") => bail!("Missing pack name, usage: pack <name>"),
+            ("pack", name) => Command::SetPack(name.to_owned()),
+            ("volume", volume) => {
+                let volume: u32 = volume
+                    .parse()
+                    .with_context(|| format!("Invalid volume '{volume}', usage: volume <1-100>"))?;
+                if !(1..=100).contains(&volume) {
+                    bail!("Volume must be between 1 and 100, got {volume}");
+                }
+                Command::SetVolume(volume)
+            }
+            ("mute", "") => Command::SetMuted(true),
+            ("unmute", "") => Command::SetMuted(false),
+            ("toggle-mute", "") => Command::ToggleMute,
+            ("status", "") => Command::Status,
+            ("mute" | "unmute" | "toggle-mute" | "status", _) => {
+                bail!("'{name}' doesn't take an argument")
+            }
+            _ => bail!("Unknown command '{line}'"),
+        };
+
+        Ok(command)
+    }
+
+    /// Formats the command the way [`Command::parse`] reads it.
+    pub fn to_line(&self) -> String {
+        match self {
+            Command::SetPack(name) => format!("pack {name}"),
+            Command::SetVolume(volume) => format!("volume {volume}"),
+            Command::SetMuted(true) => "mute".to_owned(),
+            Command::SetMuted(false) => "unmute".to_owned(),
+            Command::ToggleMute => "toggle-mute".to_owned(),
+            Command::Status => "status".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Response {
+    Ok(AudioState),
+    Error(String),
+}
+
+/// Socket the running app listens on: `$XDG_RUNTIME_DIR/whisperkeys.sock`,
+/// or `whisperkeys-<uid>/whisperkeys.sock` in the temporary directory.
+pub fn socket_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("whisperkeys.sock"),
+        None => std::env::temp_dir()
+            .join(format!("whisperkeys-{}", current_uid()))
+            .join("whisperkeys.sock"),
+    }
+}
+
+fn current_uid() -> u32 {
+    // SAFETY: getuid has no preconditions and can't fail
+    unsafe { libc::getuid() }
+}
+
+/// Creates `dir` if needed and makes sure only this user can get into it, so
+/// nobody else can reach or swap out the socket inside.
+fn private_dir(dir: &Path) -> Result<()> {
+    match fs::DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e).with_context(|| format!("Failed to create {}", dir.display())),
+    }
+
+    check_private_dir(dir)
+}
+
+fn check_private_dir(dir: &Path) -> Result<()> {
+    let metadata =
+        fs::symlink_metadata(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+    if !metadata.is_dir() || metadata.uid() != current_uid() || metadata.mode() & 0o077 != 0 {
+        bail!(
+            "{} must be a directory only you can access, refusing to use the socket in it",
+            dir.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Socket created by [`serve`], removed when this is dropped.
+#[must_use = "the socket is removed as soon as this is dropped"]
+pub struct ControlSocket {
+    path: PathBuf,
+    inode: u64,
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        // only remove it if it's still ours and not another instance's
+        let ours = fs::symlink_metadata(&self.path).is_ok_and(|m| m.ino() == self.inode);
+        if ours {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Listens on `path` in a background thread, applying commands to
+/// `audio_manager`. Packs are loaded from `packs_dir`, and `on_change` is
+/// called with the new state after each command that changed something.
+///
+/// The socket stays reachable for as long as the returned [`ControlSocket`]
+/// is kept.
+pub fn serve(
+    path: &Path,
+    audio_manager: AudioManager,
+    packs_dir: PathBuf,
+    on_change: impl Fn(&AudioState) + Send + Sync + 'static,
+) -> Result<ControlSocket> {
+    if let Some(dir) = path.parent() {
+        private_dir(dir)?;
+    }
+
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if UnixStream::connect(path).is_ok() {
+            bail!("WhisperKeys is already listening on {}", path.display());
+        }
+        if !metadata.file_type().is_socket() || metadata.uid() != current_uid() {
+            bail!(
+                "{} already exists and isn't one of your sockets, not removing it",
+                path.display()
+            );
+        }
+
+        // left behind by an instance that didn't shut down cleanly
+        fs::remove_file(path)
+            .with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+    }
+
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("Failed to listen on {}", path.display()))?;
+    let socket = ControlSocket {
+        path: path.to_path_buf(),
+        inode: fs::symlink_metadata(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?
+            .ino(),
+    };
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("Failed to restrict access to {}", path.display()))?;
+
+    let server = Arc::new(Server {
+        audio_manager,
+        packs_dir,
+        on_change: Box::new(on_change),
+    });
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("Failed to accept control connection: {e}");
+                    continue;
+                }
+            };
+
+            let server = server.clone();
+            thread::spawn(move || {
+                if let Err(e) = server.handle_client(stream) {
+                    eprintln!("Control connection failed: {e:#}");
+                }
+            });
+        }
+    });
+
+    Ok(socket)
+}
+
+/// Sends a single command to the app listening on `path` and returns its
+/// reply.
+pub fn send(path: &Path, command: &Command) -> Result<Response> {
+    if let Some(dir) = path.parent().filter(|dir| dir.exists()) {
+        check_private_dir(dir)?;
+    }
+
+    let mut stream = UnixStream::connect(path).with_context(|| {
+        format!(
+            "Couldn't connect to {}, is WhisperKeys running?",
+            path.display()
+        )
+    })?;
+    writeln!(stream, "{}", command.to_line())?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+
+    serde_json::from_str(line.trim())
+        .with_context(|| format!("Invalid reply from WhisperKeys: '{}'", line.trim()))
+}
+
+struct Server {
+    audio_manager: AudioManager,
+    packs_dir: PathBuf,
+    on_change: Box<dyn Fn(&AudioState) + Send + Sync>,
+}
+
+impl Server {
+    fn handle_client(&self, stream: UnixStream) -> Result<()> {
+        let mut writer = stream.try_clone()?;
+
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = match Command::parse(&line).and_then(|command| self.apply(command)) {
+                Ok(state) => Response::Ok(state),
+                Err(e) => Response::Error(format!("{e:#}")),
+            };
+
+            let reply = serde_json::to_string(&response).expect("responses always serialize");
+            writeln!(writer, "{reply}")?;
+        }
+
+        Ok(())
+    }
+
+    fn apply(&self, command: Command) -> Result<AudioState> {
+        let message = match command {
+            Command::SetPack(name) => {
+                AudioMessage::SetPack(Pack::load_from(&self.packs_dir, &name)?)
+            }
+            Command::SetVolume(volume) => AudioMessage::SetVolume(volume),
+            Command::SetMuted(muted) => AudioMessage::SetMuted(muted),
+            Command::ToggleMute => AudioMessage::ToggleMute,
+            Command::Status => return self.audio_manager.state(),
+        };
+
+        self.audio_manager.send(message)?;
+        let state = self.audio_manager.state()?;
+        (self.on_change)(&state);
+
+        Ok(state)
+    }
+}
@@ -0,0 +1,188 @@
+//! Where the keys sit on common keyboard layouts, to pan every key to its
+//! place on the board.
+
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, str::FromStr};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Layout {
+    /// Full size ANSI board.
+    #[default]
+    Ansi,
+    /// Full size ISO board, with the tall return key and the extra key next
+    /// to the left shift.
+    Iso,
+    /// ANSI board without the numpad.
+    Tkl,
+    /// ANSI board without the function row, navigation keys and numpad.
+    SixtyPercent,
+}
+
+impl Layout {
+    pub const ALL: [Layout; 4] = [Layout::Ansi, Layout::Iso, Layout::Tkl, Layout::SixtyPercent];
+
+    fn blocks(self) -> &'static [&'static [&'static str]] {
+        match self {
+            Layout::Ansi => &[ANSI, NAVIGATION, NUMPAD],
+            Layout::Iso => &[ISO, NAVIGATION, NUMPAD],
+            Layout::Tkl => &[ANSI, NAVIGATION],
+            Layout::SixtyPercent => &[SIXTY_PERCENT],
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Layout::Ansi => "ANSI",
+            Layout::Iso => "ISO",
+            Layout::Tkl => "TKL",
+            Layout::SixtyPercent => "60%",
+        })
+    }
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ansi" => Ok(Layout::Ansi),
+            "iso" => Ok(Layout::Iso),
+            "tkl" => Ok(Layout::Tkl),
+            "60" | "60%" | "sixty_percent" => Ok(Layout::SixtyPercent),
+            _ => Err(format!(
+                "unknown layout '{s}', expected one of ansi, iso, tkl or 60"
+            )),
+        }
+    }
+}
+
+// Blocks of keys placed side by side, one string per row. Keys are written as
+// `Name` or `Name:width` in key units, `_` is a gap, and `A/B` puts two keys
+// at the same place.
+
+const ANSI: &[&str] = &[
+    "Escape _ F1 F2 F3 F4 _:0.5 F5 F6 F7 F8 _:0.5 F9 F10 F11 F12",
+    "BackQuote Num1 Num2 Num3 Num4 Num5 Num6 Num7 Num8 Num9 Num0 Minus Equal Backspace:2",
+    "Tab:1.5 KeyQ KeyW KeyE KeyR KeyT KeyY KeyU KeyI KeyO KeyP LeftBracket RightBracket BackSlash:1.5",
+    "CapsLock:1.75 KeyA KeyS KeyD KeyF KeyG KeyH KeyJ KeyK KeyL SemiColon Quote Return:2.25",
+    "ShiftLeft:2.25 KeyZ KeyX KeyC KeyV KeyB KeyN KeyM Comma Dot Slash ShiftRight:2.75",
+    "ControlLeft:1.25 MetaLeft:1.25 Alt:1.25 Space:6.25 AltGr:1.25 MetaRight:1.25 Function:1.25 ControlRight:1.25",
+];
+
+const ISO: &[&str] = &[
+    "Escape _ F1 F2 F3 F4 _:0.5 F5 F6 F7 F8 _:0.5 F9 F10 F11 F12",
+    "BackQuote Num1 Num2 Num3 Num4 Num5 Num6 Num7 Num8 Num9 Num0 Minus Equal Backspace:2",
+    "Tab:1.5 KeyQ KeyW KeyE KeyR KeyT KeyY KeyU KeyI KeyO KeyP LeftBracket RightBracket",
+    "CapsLock:1.75 KeyA KeyS KeyD KeyF KeyG KeyH KeyJ KeyK KeyL SemiColon Quote BackSlash Return:1.25",
+    "ShiftLeft:1.25 IntlBackslash KeyZ KeyX KeyC KeyV KeyB KeyN KeyM Comma Dot Slash ShiftRight:2.75",
+    "ControlLeft:1.25 MetaLeft:1.25 Alt:1.25 Space:6.25 AltGr:1.25 MetaRight:1.25 Function:1.25 ControlRight:1.25",
+];
+
+const SIXTY_PERCENT: &[&str] = &[
+    "Escape/BackQuote Num1 Num2 Num3 Num4 Num5 Num6 Num7 Num8 Num9 Num0 Minus Equal Backspace:2",
+    "Tab:1.5 KeyQ KeyW KeyE KeyR KeyT KeyY KeyU KeyI KeyO KeyP LeftBracket RightBracket BackSlash:1.5",
+    "CapsLock:1.75 KeyA KeyS KeyD KeyF KeyG KeyH KeyJ KeyK KeyL SemiColon Quote Return:2.25",
+    "ShiftLeft:2.25 KeyZ KeyX KeyC KeyV KeyB KeyN KeyM Comma Dot Slash ShiftRight:2.75",
+    "ControlLeft:1.25 MetaLeft:1.25 Alt:1.25 Space:6.25 AltGr:1.25 MetaRight:1.25 Function:1.25 ControlRight:1.25",
+];
+
+const NAVIGATION: &[&str] = &[
+    "PrintScreen ScrollLock Pause",
+    "Insert Home PageUp",
+    "Delete End PageDown",
+    "_ UpArrow",
+    "LeftArrow DownArrow RightArrow",
+];
+
+const NUMPAD: &[&str] = &[
+    "NumLock KpDivide KpMultiply KpMinus",
+    "Kp7 Kp8 Kp9 KpPlus",
+    "Kp4 Kp5 Kp6",
+    "Kp1 Kp2 Kp3 KpReturn",
+    "Kp0:2 KpDelete",
+];
+
+/// Space between two blocks, in key units.
+const BLOCK_GAP: f32 = 0.25;
+
+/// Horizontal position of every key of a layout, from -1 at the left edge of
+/// the board to 1 at its right edge.
+#[derive(Debug, Clone)]
+pub struct PanMap {
+    positions: HashMap<String, f32>,
+}
+
+impl PanMap {
+    pub fn new(layout: Layout) -> Self {
+        let mut centers = HashMap::new();
+        let mut offset = 0.0;
+
+        for block in layout.blocks() {
+            let mut block_width: f32 = 0.0;
+
+            for row in *block {
+                let mut x = offset;
+
+                for slot in row.split_whitespace() {
+                    let (names, width) = match slot.split_once(':') {
+                        Some((names, width)) => (names, width.parse().unwrap_or(1.0)),
+                        None => (slot, 1.0),
+                    };
+
+                    if names != "_" {
+                        for name in names.split('/') {
+                            centers.insert(name.to_owned(), x + width / 2.0);
+                        }
+                    }
+                    x += width;
+                }
+
+                block_width = block_width.max(x - offset);
+            }
+
+            offset += block_width + BLOCK_GAP;
+        }
+
+        let width = offset - BLOCK_GAP;
+        let positions = centers
+            .into_iter()
+            .map(|(name, center)| (name, center / width * 2.0 - 1.0))
+            .collect();
+
+        Self { positions }
+    }
+
+    /// Position of `key`, `None` if the layout doesn't have it.
+    pub fn position(&self, key: &str) -> Option<f32> {
+        self.positions.get(key).copied()
+    }
+}
+
+/// How keys are spread out when the spatial mode is on.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Spatial {
+    /// Off keeps the other settings for when the mode is turned back on.
+    pub enabled: bool,
+    pub layout: Layout,
+    /// How far the sides of the board are panned, from 0 (every key in the
+    /// middle) to 1 (the outermost keys fully on one side).
+    pub width: f32,
+    /// Volume drop in dB of the outermost keys, the ones in between are
+    /// attenuated in proportion to their distance from the middle.
+    pub attenuation: f32,
+}
+
+impl Default for Spatial {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            layout: Layout::default(),
+            width: 0.6,
+            attenuation: 0.0,
+        }
+    }
+}
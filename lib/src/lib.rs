@@ -1,2 +1,12 @@
+pub mod archive;
 pub mod audio_manager;
+#[cfg(unix)]
+pub mod control;
+pub mod layout;
 pub mod pack;
+pub mod render;
+pub mod settings;
+pub mod validate;
+pub mod watch;
+
+pub use protocol::{self, keys};
@@ -2,50 +2,233 @@ use anyhow::Context;
 use rayon::prelude::*;
 use rdev::Key;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
 
 use anyhow::Result;
 use kira::sound::static_sound::StaticSoundData;
 
+use crate::{
+    archive::{self, PackArchive},
+    keys,
+};
+
 #[derive(Serialize, Deserialize)]
-struct RawPack {
-    creator: String,
-    source: String,
-    default_volume: String,
-    keys: HashMap<String, String>,
+pub(crate) struct RawPack {
+    pub creator: String,
+    pub source: String,
+    pub default_volume: String,
+    pub keys: HashMap<String, SoundFiles>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub release_keys: HashMap<String, SoundFiles>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_release: Option<SoundFiles>,
+    #[serde(default)]
+    pub sample_selection: SampleSelection,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub pan: HashMap<String, f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variation: Option<Variation>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cut_previous: Vec<String>,
 }
 
-#[derive(Debug)]
+/// A key entry in the config: either a single sound or a list of sounds to
+/// choose from on every press.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum SoundFiles {
+    Single(SoundSource),
+    Multiple(Vec<SoundSource>),
+}
+
+impl SoundFiles {
+    pub fn sources(&self) -> &[SoundSource] {
+        match self {
+            SoundFiles::Single(source) => std::slice::from_ref(source),
+            SoundFiles::Multiple(sources) => sources,
+        }
+    }
+}
+
+/// A whole audio file, or a region of one for sprite-sheet packs where every
+/// key lives in the same file. `start` and `duration` are in milliseconds.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum SoundSource {
+    File(String),
+    Region {
+        file: String,
+        start: f64,
+        duration: f64,
+    },
+}
+
+impl SoundSource {
+    pub fn file(&self) -> &str {
+        match self {
+            SoundSource::File(file) | SoundSource::Region { file, .. } => file,
+        }
+    }
+}
+
+/// How a sample is chosen when a key has more than one.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleSelection {
+    /// Random sample, never the same one twice in a row.
+    #[default]
+    Random,
+    /// Cycle through the samples in the order they are listed.
+    RoundRobin,
+}
+
+/// Random pitch and volume change applied to every sample played.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Variation {
+    /// Largest pitch change either way, in semitones.
+    pub pitch: f64,
+    /// Largest volume change either way, in dB.
+    pub volume: f32,
+    pub distribution: Distribution,
+}
+
+impl Default for Variation {
+    fn default() -> Self {
+        Self {
+            pitch: 0.25,
+            volume: 1.0,
+            distribution: Distribution::default(),
+        }
+    }
+}
+
+/// How the changes are spread over their range.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Distribution {
+    /// Every change in the range is as likely.
+    #[default]
+    Uniform,
+    /// Small changes are more likely than large ones.
+    Triangular,
+}
+
+impl Distribution {
+    pub const ALL: [Distribution; 2] = [Distribution::Uniform, Distribution::Triangular];
+
+    /// Picks a value in [-1, 1] from `uniform`, which gives values in [0, 1).
+    pub(crate) fn sample(self, mut uniform: impl FnMut() -> f64) -> f64 {
+        match self {
+            Distribution::Uniform => uniform() * 2.0 - 1.0,
+            Distribution::Triangular => uniform() - uniform(),
+        }
+    }
+}
+
+impl std::fmt::Display for Distribution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Distribution::Uniform => "Uniform",
+            Distribution::Triangular => "Triangular",
+        })
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct Pack {
     pub name: String,
+    pub creator: String,
+    pub source: String,
     pub default_volume: u32,
-    pub keys: HashMap<String, StaticSoundData>,
+    /// Samples for each key, every key has at least one.
+    pub keys: HashMap<String, Vec<StaticSoundData>>,
+    /// Sounds played when a key goes back up, looked up like `keys`.
+    pub release_keys: HashMap<String, Vec<StaticSoundData>>,
+    /// Release samples used for keys that have no entry in `release_keys`,
+    /// empty if the pack doesn't define any.
+    pub default_release: Vec<StaticSoundData>,
+    pub sample_selection: SampleSelection,
+    /// Positions from -1 (left) to 1 (right) used instead of the layout's in
+    /// the spatial mode, for packs recorded on a board of their own.
+    pub pan: HashMap<String, f32>,
+    pub variation: Variation,
+    /// Keys whose press cuts off the sound of their previous press, like a
+    /// real switch that can't sound twice at once.
+    pub cut_previous: HashSet<String>,
 }
 
 impl Pack {
+    /// Loads the pack `pack_name` from `folder`, either a pack folder or a
+    /// pack archive.
     pub fn load_from(folder: &Path, pack_name: &str) -> Result<Self> {
         let path = folder.join(pack_name);
 
-        let config = Self::read_config_file(&path)?;
+        let mut archive = if archive::is_archive(&path) && path.is_file() {
+            Some(PackArchive::open(&path)?)
+        } else {
+            None
+        };
+
+        let config = match &mut archive {
+            Some(archive) => archive.read_config()?,
+            None => Self::read_config_file(&path)?,
+        };
         let parsed_config: RawPack = json5::from_str(&config)
             .with_context(|| format!("Invalid configuration file in {}", path.display()))?;
 
-        let pack_keys = parsed_config
+        let default_release = parsed_config
+            .default_release
+            .as_ref()
+            .map_or(&[][..], SoundFiles::sources)
+            .iter()
+            .filter(|source| !source.file().is_empty())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        // Decode every referenced file once, sprite packs point all their keys
+        // at the same one.
+        let files = parsed_config
             .keys
-            .par_iter()
-            .map(|(key, value)| {
-                let filepath = path.join(value);
-
-                let sound_data = StaticSoundData::from_file(&filepath).with_context(|| {
-                    format!(
-                        "Failed to load sound for key '{key}' from '{}'",
-                        filepath.display()
-                    )
-                })?;
-
-                Ok((key.to_owned(), sound_data))
-            })
-            .collect::<Result<HashMap<_, _>>>()?;
+            .values()
+            .chain(parsed_config.release_keys.values())
+            .flat_map(SoundFiles::sources)
+            .chain(&default_release)
+            .map(SoundSource::file)
+            .collect::<HashSet<_>>();
+
+        let decoded = match &mut archive {
+            // the archive can only be read one file at a time, decoding can
+            // still happen in parallel
+            Some(archive) => files
+                .into_iter()
+                .map(|file| Ok((file, archive.read(file)?)))
+                .collect::<Result<Vec<_>>>()?
+                .into_par_iter()
+                .map(|(file, bytes)| {
+                    let sound = StaticSoundData::from_cursor(Cursor::new(bytes))
+                        .with_context(|| format!("Failed to load sound from '{file}'"))?;
+                    Ok((file, sound))
+                })
+                .collect::<Result<HashMap<_, _>>>()?,
+            None => files
+                .into_par_iter()
+                .map(|file| Ok((file, Self::load_sound(&path, file)?)))
+                .collect::<Result<HashMap<_, _>>>()?,
+        };
+
+        let pack_keys = Self::resolve_sounds(&decoded, &parsed_config.keys)?;
+        let release_keys = Self::resolve_sounds(&decoded, &parsed_config.release_keys)?;
+        let default_release = default_release
+            .iter()
+            .map(|source| Self::resolve_source(&decoded, source))
+            .collect::<Result<Vec<_>>>()
+            .context("Invalid default release sound")?;
 
         let default_volume = parsed_config.default_volume.parse().with_context(|| {
             format!("Invalid default_volume: '{}'", parsed_config.default_volume)
@@ -53,49 +236,154 @@ impl Pack {
 
         Ok(Pack {
             name: pack_name.to_owned(),
+            creator: parsed_config.creator,
+            source: parsed_config.source,
             default_volume,
             keys: pack_keys,
+            release_keys,
+            default_release,
+            sample_selection: parsed_config.sample_selection,
+            pan: parsed_config.pan,
+            variation: parsed_config.variation.unwrap_or_default(),
+            cut_previous: parsed_config.cut_previous.into_iter().collect(),
         })
     }
 
-    fn read_config_file(path: &Path) -> Result<String> {
+    fn resolve_sounds(
+        decoded: &HashMap<&str, StaticSoundData>,
+        files: &HashMap<String, SoundFiles>,
+    ) -> Result<HashMap<String, Vec<StaticSoundData>>> {
+        files
+            .iter()
+            .map(|(key, value)| {
+                if value.sources().is_empty() {
+                    anyhow::bail!("Key '{key}' has an empty list of sounds");
+                }
+
+                let samples = value
+                    .sources()
+                    .iter()
+                    .map(|source| Self::resolve_source(decoded, source))
+                    .collect::<Result<Vec<_>>>()
+                    .with_context(|| format!("Invalid sound for key '{key}'"))?;
+
+                Ok((key.to_owned(), samples))
+            })
+            .collect()
+    }
+
+    pub(crate) fn resolve_source(
+        decoded: &HashMap<&str, StaticSoundData>,
+        source: &SoundSource,
+    ) -> Result<StaticSoundData> {
+        // every file was decoded beforehand
+        let sound = decoded[source.file()].clone();
+
+        let SoundSource::Region {
+            file,
+            start,
+            duration,
+        } = source
+        else {
+            return Ok(sound);
+        };
+
+        let total = sound.duration().as_secs_f64();
+        let start = start / 1000.0;
+        // Mechvibes sprites often overshoot the end of the file by a few ms
+        let end = (start + duration / 1000.0).min(total);
+
+        if *duration <= 0.0 || start < 0.0 || start >= total {
+            anyhow::bail!(
+                "Region starting at {}ms for {duration}ms is outside of '{file}' ({}ms long)",
+                start * 1000.0,
+                (total * 1000.0).round()
+            );
+        }
+
+        Ok(sound.slice(start..end))
+    }
+
+    pub(crate) fn load_sound(path: &Path, file: &str) -> Result<StaticSoundData> {
+        let filepath = path.join(file);
+
+        StaticSoundData::from_file(&filepath)
+            .with_context(|| format!("Failed to load sound from '{}'", filepath.display()))
+    }
+
+    pub(crate) fn read_config_file(path: &Path) -> Result<String> {
         fs::read_to_string(path.join("config.json5"))
             .or_else(|_| fs::read_to_string(path.join("config.json")))
             .with_context(|| format!("No config file found at path {}", path.display()))
     }
 }
 
+/// The folder packs are installed in, `~/WhisperKeys`.
+pub fn default_packs_dir() -> Result<PathBuf> {
+    let home = std::env::home_dir().context("Couldn't get the user's home directory")?;
+    Ok(home.join("WhisperKeys"))
+}
+
 /// Create a new pack folder inside `base_path` and populate it with a default
-/// `config.json5` copied from the embedded template.
-pub fn create_new_pack(base_path: &Path) -> Result<()> {
-    // Find an available folder name: "New pack", "New pack (2)", ...
-    let mut folder_name = String::from("New pack");
-    let mut candidate_path = base_path.join(&folder_name);
-    let mut counter: u32 = 1;
+/// `config.json5` copied from the embedded template. The folder is named
+/// "New pack", or "New pack (2)" and so on if that one is taken.
+pub fn create_new_pack(base_path: &Path) -> Result<PathBuf> {
+    let pack_path = create_unique_dir(base_path, "New pack")?;
+    write_template(&pack_path)?;
+
+    Ok(pack_path)
+}
+
+/// Create the pack folder `name` inside `base_path` from the embedded
+/// template, failing if it already exists.
+pub fn create_pack(base_path: &Path, name: &str) -> Result<PathBuf> {
+    let pack_path = base_path.join(name);
 
-    while candidate_path.exists() {
-        counter += 1;
-        folder_name = format!("New pack ({counter})");
-        candidate_path = base_path.join(&folder_name);
+    if pack_path.exists() {
+        anyhow::bail!("{} already exists", pack_path.display());
     }
 
-    fs::create_dir_all(&candidate_path).with_context(|| {
-        format!(
-            "Failed to create pack directory at {}",
-            candidate_path.display()
-        )
-    })?;
+    fs::create_dir_all(&pack_path)
+        .with_context(|| format!("Failed to create pack directory at {}", pack_path.display()))?;
+    write_template(&pack_path)?;
+
+    Ok(pack_path)
+}
 
+fn write_template(pack_path: &Path) -> Result<()> {
     // Embed the template at compile time and write it as config.json5
     let template_contents = include_str!("config_template.json5");
-    fs::write(candidate_path.join("config.json5"), template_contents).with_context(|| {
-        format!(
-            "Failed to write config.json5 into {}",
-            candidate_path.display()
-        )
-    })?;
+    fs::write(pack_path.join("config.json5"), template_contents)
+        .with_context(|| format!("Failed to write config.json5 into {}", pack_path.display()))
+}
 
-    Ok(())
+/// Creates an empty folder named `name` inside `base_path`, or "`name` (2)"
+/// and so on if that one is taken.
+fn create_unique_dir(base_path: &Path, name: &str) -> Result<PathBuf> {
+    fs::create_dir_all(base_path)
+        .with_context(|| format!("Failed to create {}", base_path.display()))?;
+
+    let mut folder_name = name.to_owned();
+    let mut counter: u32 = 1;
+
+    loop {
+        let path = base_path.join(&folder_name);
+
+        // create_dir fails if the folder exists, so two conversions running at
+        // once can't end up in the same folder
+        match fs::create_dir(&path) {
+            Ok(()) => return Ok(path),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                counter += 1;
+                folder_name = format!("{name} ({counter})");
+            }
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!("Failed to create pack directory at {}", path.display())
+                });
+            }
+        }
+    }
 }
 
 pub fn list_installed(path: &Path) -> Result<Vec<String>> {
@@ -108,6 +396,16 @@ pub fn list_installed(path: &Path) -> Result<Vec<String>> {
         let entry = entry.with_context(|| "Failed to read directory entry")?;
         let entry_path = entry.path();
 
+        // packs being installed are unpacked in hidden folders
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        if entry_path.is_file() && archive::is_archive(&entry_path) {
+            packs.push(entry.file_name().to_string_lossy().into_owned());
+            continue;
+        }
+
         if !entry_path.is_dir() {
             continue;
         }
@@ -127,191 +425,586 @@ fn has_config_file(dir_path: &Path) -> Result<bool> {
     Ok(dir_path.join("config.json5").exists() || dir_path.join("config.json").exists())
 }
 
-pub fn from_mechvibes(path: &Path) -> Result<()> {
+/// Outcome of a Mechvibes conversion.
+#[derive(Debug)]
+pub struct ConversionReport {
+    /// Name of the new pack in the packs folder.
+    pub pack_name: String,
+    pub path: PathBuf,
+    /// Keycodes of the Mechvibes config that don't match a key. Their sounds
+    /// are used for the "Unknown" fallback.
+    pub unmapped_codes: Vec<String>,
+    /// Keycodes whose value doesn't fit the pack's `key_define_type`, a file
+    /// name in a "single" pack or a region in a "multi" one. They are left
+    /// out.
+    pub mismatched_codes: Vec<String>,
+    /// No keycode fell back to "Unknown", so keys missing from the pack will
+    /// be silent.
+    pub missing_fallback: bool,
+}
+
+/// Converts the Mechvibes pack in `source` into a new pack in `packs_dir`,
+/// named after the Mechvibes pack. The audio files are copied over and
+/// `source` is left untouched.
+pub fn from_mechvibes(source: &Path, packs_dir: &Path) -> Result<ConversionReport> {
     #[derive(Deserialize)]
     struct MechvibesPack {
-        defines: HashMap<String, Option<String>>,
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(default)]
+        key_define_type: Option<String>,
+        #[serde(default)]
+        sound: Option<String>,
+        defines: BTreeMap<String, Option<MechvibesDefine>>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum MechvibesDefine {
+        /// "multi" packs: one file per key
+        File(String),
+        /// "single" packs: `[start_ms, duration_ms]` in the shared `sound` file
+        Region([f64; 2]),
     }
 
-    let config_path = path.join("config.json");
+    let config_path = source.join("config.json");
 
     let config = fs::read_to_string(&config_path)
-        .with_context(|| format!("Config file not found at path '{}'", path.display()))?;
+        .with_context(|| format!("Config file not found at path '{}'", source.display()))?;
 
     let parsed: MechvibesPack = serde_json::from_str(&config)
-        .with_context(|| format!("Config at path '{}' is not valid", path.display()))?;
-
-    let keys: HashMap<String, String> = parsed
-        .defines
-        .into_iter()
-        .filter_map(|(key, value)| {
-            let value = value?;
+        .with_context(|| format!("Config at path '{}' is not valid", source.display()))?;
+
+    let sprite = match parsed.key_define_type.as_deref() {
+        None | Some("multi") => None,
+        Some("single") => Some(parsed.sound.with_context(|| {
+            format!(
+                "Config at path '{}' uses key_define_type \"single\" but has no sound file",
+                source.display()
+            )
+        })?),
+        Some(other) => anyhow::bail!("Unsupported key_define_type \"{other}\""),
+    };
 
-            let keycode = key.parse::<u16>().ok()?;
-            let key = key_from_code(keycode);
+    // Several keycodes can map to the same key (e.g. both dot keys, or every
+    // unmapped code to "Unknown"), their sounds become alternative samples.
+    let mut samples: HashMap<String, Vec<SoundSource>> = HashMap::new();
+    let mut unmapped_codes = Vec::new();
+    let mut mismatched_codes = Vec::new();
+
+    for (code, value) in parsed.defines {
+        let source = match (value, &sprite) {
+            // Mechvibes' way of leaving a key silent
+            (None, _) => continue,
+            (Some(MechvibesDefine::File(file)), None) => SoundSource::File(file),
+            (Some(MechvibesDefine::Region([start, duration])), Some(file)) => SoundSource::Region {
+                file: file.clone(),
+                start,
+                duration,
+            },
+            (Some(_), _) => {
+                mismatched_codes.push(code);
+                continue;
+            }
+        };
+
+        let key = match code.parse::<u16>().map(key_from_code) {
+            Ok(Key::Unknown(_)) | Err(_) => {
+                unmapped_codes.push(code);
+                String::from(keys::UNKNOWN_KEY)
+            }
+            Ok(key) => keys::key_name(key),
+        };
+
+        let sources = samples.entry(key).or_default();
+        if !sources.contains(&source) {
+            sources.push(source);
+        }
+    }
 
-            let key_str = match key {
-                Key::Unknown(_) => String::from("Unknown"),
-                _ => format!("{key:?}"),
+    let files = samples
+        .values()
+        .flatten()
+        .map(SoundSource::file)
+        .collect::<HashSet<_>>();
+
+    let keys: HashMap<String, SoundFiles> = samples
+        .iter()
+        .map(|(key, sources)| {
+            let sources = match sources.as_slice() {
+                [source] => SoundFiles::Single(source.clone()),
+                sources => SoundFiles::Multiple(sources.to_vec()),
             };
 
-            Some((key_str, value))
+            (key.clone(), sources)
         })
         .collect();
 
-    if !keys.contains_key("Unknown") {
-        eprintln!(
-            "WARNING: No unknown key found in the config. \
-            This means that the keylogger will not be able to \
-            detect unknown keys. And will probably crash \
-            Please add a key named \"unknown\" to your config."
-        );
-    }
+    let missing_fallback = !keys.contains_key(keys::UNKNOWN_KEY);
 
     let pack = RawPack {
         creator: String::new(),
         source: String::new(),
         default_volume: "50".to_string(),
         keys,
+        release_keys: HashMap::new(),
+        default_release: None,
+        sample_selection: SampleSelection::default(),
+        pan: HashMap::new(),
+        variation: None,
+        cut_previous: Vec::new(),
     };
 
     let serialized =
         serde_json::to_string_pretty(&pack).context("Failed to serialize pack configuration")?;
 
-    let backup_path = config_path.with_extension("json.bak");
-    fs::rename(&config_path, &backup_path)
-        .with_context(|| format!("Failed to create backup at {}", backup_path.display()))?;
+    let name = parsed
+        .name
+        .as_deref()
+        .map(folder_name)
+        .filter(|name| !name.is_empty())
+        .or_else(|| {
+            source
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| String::from("Converted pack"));
+
+    let pack_path = create_unique_dir(packs_dir, &name)?;
+
+    let written = files
+        .iter()
+        .try_for_each(|file| copy_pack_file(source, &pack_path, file))
+        .and_then(|()| {
+            fs::write(pack_path.join("config.json5"), serialized)
+                .context("Failed to write new config file")
+        });
+
+    if let Err(e) = written {
+        // don't leave a half converted pack in the list
+        let _ = fs::remove_dir_all(&pack_path);
+        return Err(e);
+    }
+
+    let pack_name = pack_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or(name);
+
+    Ok(ConversionReport {
+        pack_name,
+        path: pack_path,
+        unmapped_codes,
+        mismatched_codes,
+        missing_fallback,
+    })
+}
+
+/// Converts every Mechvibes pack found directly inside `library` into
+/// `packs_dir`, in parallel. A failing pack doesn't stop the others, the
+/// result of each one is returned along with its folder, sorted by folder.
+pub fn from_mechvibes_library(
+    library: &Path,
+    packs_dir: &Path,
+) -> Result<Vec<(PathBuf, Result<ConversionReport>)>> {
+    let entries = fs::read_dir(library)
+        .with_context(|| format!("Failed to read directory: {}", library.display()))?;
+
+    let mut sources = Vec::new();
+    for entry in entries {
+        let path = entry.context("Failed to read directory entry")?.path();
+        if path.join("config.json").is_file() {
+            sources.push(path);
+        }
+    }
+    sources.sort();
+
+    let results = sources
+        .into_par_iter()
+        .map(|source| {
+            let result = from_mechvibes(&source, packs_dir);
+            (source, result)
+        })
+        .collect();
+
+    Ok(results)
+}
 
-    fs::write(path.join("config.json5"), serialized).context("Failed to write new config file")?;
+/// Outcome of an export to Mechvibes.
+#[derive(Debug)]
+pub struct MechvibesExport {
+    pub path: PathBuf,
+    /// Keys of the pack that have no Mechvibes keycode, left out of the export.
+    pub unmapped_keys: Vec<String>,
+    /// Keys with several samples, only their first one is exported.
+    pub trimmed_keys: Vec<String>,
+    /// Keys playing a region of a file, which Mechvibes' multi mode can't do.
+    /// They are left out of the export.
+    pub region_keys: Vec<String>,
+    /// Keys with an empty list of sounds, which never play. They are left out
+    /// of the export.
+    pub silent_keys: Vec<String>,
+    /// The pack has release sounds, Mechvibes doesn't play any.
+    pub dropped_release: bool,
+}
 
+/// Writes the pack in `folder`, a pack folder or archive, as a new Mechvibes
+/// pack in `dest`, in "multi" mode with one file per keycode. Keycodes the
+/// pack has no sound for use its "Unknown" sound, like they do in WhisperKeys.
+pub fn to_mechvibes(folder: &Path, dest: &Path) -> Result<MechvibesExport> {
+    #[derive(Serialize)]
+    struct MechvibesPack {
+        id: String,
+        name: String,
+        key_define_type: &'static str,
+        includes_numpad: bool,
+        sound: String,
+        defines: BTreeMap<u16, Option<String>>,
+    }
+
+    if archive::is_archive(folder) && folder.is_file() {
+        let (_temp, unpacked) = archive::unpack_to_temp(folder)?;
+        return to_mechvibes(&unpacked, dest);
+    }
+
+    let config = Pack::read_config_file(folder)?;
+    let raw: RawPack = json5::from_str(&config)
+        .with_context(|| format!("Invalid configuration file in {}", folder.display()))?;
+
+    let name = folder
+        .file_name()
+        .with_context(|| format!("{} is not a pack folder", folder.display()))?
+        .to_string_lossy()
+        .into_owned();
+
+    let mut sounds = HashMap::new();
+    let mut unmapped_keys = Vec::new();
+    let mut trimmed_keys = Vec::new();
+    let mut region_keys = Vec::new();
+    let mut silent_keys = Vec::new();
+
+    // sorted so the report reads the same on every run
+    for (key, files) in raw.keys.iter().collect::<BTreeMap<_, _>>() {
+        let file = match files.sources().first() {
+            Some(SoundSource::File(file)) => file,
+            Some(SoundSource::Region { .. }) => {
+                region_keys.push(key.clone());
+                continue;
+            }
+            None => {
+                silent_keys.push(key.clone());
+                continue;
+            }
+        };
+        if files.sources().len() > 1 {
+            trimmed_keys.push(key.clone());
+        }
+
+        let target = match keys::key_from_name(key) {
+            _ if key == keys::UNKNOWN_KEY => None,
+            Some(key) if code_from_key(key).is_some() => Some(key),
+            _ => {
+                unmapped_keys.push(key.clone());
+                continue;
+            }
+        };
+        sounds.insert(target, file.clone());
+    }
+
+    let fallback = sounds.get(&None);
+    let defines = MECHVIBES_CODES
+        .iter()
+        .filter_map(|(code, key)| {
+            let file = sounds.get(&Some(*key)).or(fallback)?;
+            Some((*code, Some(file.clone())))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    if defines.is_empty() {
+        anyhow::bail!("The pack has no sound Mechvibes can play");
+    }
+
+    let includes_numpad = sounds.keys().flatten().any(|key| {
+        matches!(
+            key,
+            Key::NumLock
+                | Key::KpDivide
+                | Key::KpMultiply
+                | Key::KpMinus
+                | Key::KpPlus
+                | Key::KpReturn
+                | Key::KpDelete
+                | Key::KpEqual
+                | Key::KpComma
+                | Key::Kp0
+                | Key::Kp1
+                | Key::Kp2
+                | Key::Kp3
+                | Key::Kp4
+                | Key::Kp5
+                | Key::Kp6
+                | Key::Kp7
+                | Key::Kp8
+                | Key::Kp9
+        )
+    });
+
+    // Mechvibes names its custom packs after the time they were created
+    let created = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+
+    let pack = MechvibesPack {
+        id: format!("custom-sound-pack-{created}"),
+        name: name.clone(),
+        key_define_type: "multi",
+        includes_numpad,
+        sound: String::new(),
+        defines,
+    };
+    let serialized =
+        serde_json::to_string_pretty(&pack).context("Failed to serialize Mechvibes config")?;
+
+    let files = sounds.values().collect::<HashSet<_>>();
+    let pack_path = create_unique_dir(dest, &name)?;
+
+    let written = files
+        .iter()
+        .try_for_each(|file| copy_pack_file(folder, &pack_path, file))
+        .and_then(|()| {
+            fs::write(pack_path.join("config.json"), serialized)
+                .context("Failed to write Mechvibes config file")
+        });
+
+    if let Err(e) = written {
+        let _ = fs::remove_dir_all(&pack_path);
+        return Err(e);
+    }
+
+    let dropped_release = !raw.release_keys.is_empty()
+        || raw
+            .default_release
+            .as_ref()
+            .is_some_and(|release| release.sources().iter().any(|s| !s.file().is_empty()));
+
+    Ok(MechvibesExport {
+        path: pack_path,
+        unmapped_keys,
+        trimmed_keys,
+        region_keys,
+        silent_keys,
+        dropped_release,
+    })
+}
+
+/// Copies `file`, relative to the pack folder `from`, to the same place in
+/// `to`.
+fn copy_pack_file(from: &Path, to: &Path, file: &str) -> Result<()> {
+    let relative = Path::new(file);
+    if relative.is_absolute()
+        || relative
+            .components()
+            .any(|component| matches!(component, std::path::Component::ParentDir))
+    {
+        anyhow::bail!("'{file}' is outside of the pack folder");
+    }
+
+    let target = to.join(relative);
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::copy(from.join(relative), &target).with_context(|| format!("Failed to copy '{file}'"))?;
     Ok(())
 }
 
-// from https://github.com/hainguyents13/mechvibes/blob/master/src/libs/keycodes.js
+/// Turns a pack name into a folder name that is valid on every platform.
+fn folder_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim_matches(|c: char| c == '.' || c.is_whitespace())
+        .to_owned()
+}
+
+/// Mechvibes keycodes, which are libuiohook's virtual keycodes, and the key
+/// each one is played as. Keys with several codes list their main one first.
+///
+/// Codes rdev has no key for (media playback, browser and app launch keys...)
+/// are left out and play as unknown.
+// from https://github.com/kwhat/libuiohook/blob/1.2/include/uiohook.h
+const MECHVIBES_CODES: &[(u16, Key)] = &[
+    (0x0001, Key::Escape),
+    // function row
+    (0x003B, Key::F1),
+    (0x003C, Key::F2),
+    (0x003D, Key::F3),
+    (0x003E, Key::F4),
+    (0x003F, Key::F5),
+    (0x0040, Key::F6),
+    (0x0041, Key::F7),
+    (0x0042, Key::F8),
+    (0x0043, Key::F9),
+    (0x0044, Key::F10),
+    (0x0057, Key::F11),
+    (0x0058, Key::F12),
+    (0x005B, Key::F13),
+    (0x005C, Key::F14),
+    (0x005D, Key::F15),
+    (0x0063, Key::F16),
+    (0x0064, Key::F17),
+    (0x0065, Key::F18),
+    (0x0066, Key::F19),
+    (0x0067, Key::F20),
+    (0x0068, Key::F21),
+    (0x0069, Key::F22),
+    (0x006A, Key::F23),
+    (0x006B, Key::F24),
+    // number row
+    (0x0029, Key::BackQuote),
+    (0x0002, Key::Num1),
+    (0x0003, Key::Num2),
+    (0x0004, Key::Num3),
+    (0x0005, Key::Num4),
+    (0x0006, Key::Num5),
+    (0x0007, Key::Num6),
+    (0x0008, Key::Num7),
+    (0x0009, Key::Num8),
+    (0x000A, Key::Num9),
+    (0x000B, Key::Num0),
+    (0x000C, Key::Minus),
+    (0x000D, Key::Equal),
+    (0x000E, Key::Backspace),
+    // letters and punctuation
+    (0x000F, Key::Tab),
+    (0x003A, Key::CapsLock),
+    (0x001E, Key::KeyA),
+    (0x0030, Key::KeyB),
+    (0x002E, Key::KeyC),
+    (0x0020, Key::KeyD),
+    (0x0012, Key::KeyE),
+    (0x0021, Key::KeyF),
+    (0x0022, Key::KeyG),
+    (0x0023, Key::KeyH),
+    (0x0017, Key::KeyI),
+    (0x0024, Key::KeyJ),
+    (0x0025, Key::KeyK),
+    (0x0026, Key::KeyL),
+    (0x0032, Key::KeyM),
+    (0x0031, Key::KeyN),
+    (0x0018, Key::KeyO),
+    (0x0019, Key::KeyP),
+    (0x0010, Key::KeyQ),
+    (0x0013, Key::KeyR),
+    (0x001F, Key::KeyS),
+    (0x0014, Key::KeyT),
+    (0x0016, Key::KeyU),
+    (0x002F, Key::KeyV),
+    (0x0011, Key::KeyW),
+    (0x002D, Key::KeyX),
+    (0x0015, Key::KeyY),
+    (0x002C, Key::KeyZ),
+    (0x001A, Key::LeftBracket),
+    (0x001B, Key::RightBracket),
+    (0x002B, Key::BackSlash),
+    (0x0027, Key::SemiColon),
+    (0x0028, Key::Quote),
+    (0x001C, Key::Return),
+    (0x0033, Key::Comma),
+    (0x0034, Key::Dot),
+    (0x0035, Key::Slash),
+    (0x0039, Key::Space),
+    // the extra key of ISO boards, reported as either code depending on the
+    // libuiohook version
+    (0x0056, Key::IntlBackslash),
+    (0x0E46, Key::IntlBackslash),
+    // modifiers
+    (0x002A, Key::ShiftLeft),
+    (0x0036, Key::ShiftRight),
+    (0x001D, Key::ControlLeft),
+    (0x0E1D, Key::ControlRight),
+    (0x0038, Key::Alt),
+    (0x0E38, Key::AltGr),
+    (0x0E5B, Key::MetaLeft),
+    (0x0E5C, Key::MetaRight),
+    // navigation
+    (0x0E37, Key::PrintScreen),
+    (0x0046, Key::ScrollLock),
+    (0x0E45, Key::Pause),
+    (0x0E52, Key::Insert),
+    // older Mechvibes packs use this one for Insert
+    (0x0E34, Key::Insert),
+    (0x0E53, Key::Delete),
+    (0x0E47, Key::Home),
+    (0x0E4F, Key::End),
+    (0x0E49, Key::PageUp),
+    (0x0E51, Key::PageDown),
+    (0xE048, Key::UpArrow),
+    (0xE04B, Key::LeftArrow),
+    (0xE04D, Key::RightArrow),
+    (0xE050, Key::DownArrow),
+    // numpad
+    (0x0045, Key::NumLock),
+    (0x0E35, Key::KpDivide),
+    (0x0037, Key::KpMultiply),
+    (0x004A, Key::KpMinus),
+    (0x004E, Key::KpPlus),
+    (0x0E1C, Key::KpReturn),
+    (0x0053, Key::KpDelete),
+    (0x004F, Key::Kp1),
+    (0x0050, Key::Kp2),
+    (0x0051, Key::Kp3),
+    (0x004B, Key::Kp4),
+    (0x004C, Key::Kp5),
+    (0x004D, Key::Kp6),
+    (0x0047, Key::Kp7),
+    (0x0048, Key::Kp8),
+    (0x0049, Key::Kp9),
+    (0x0052, Key::Kp0),
+    (0x0E0D, Key::KpEqual),
+    (0x007E, Key::KpComma),
+    // japanese keys
+    (0x0073, Key::IntlRo),
+    (0x007D, Key::IntlYen),
+    (0x0070, Key::KanaMode),
+    // volume and system keys
+    (0xE020, Key::VolumeMute),
+    (0xE02E, Key::VolumeDown),
+    (0xE030, Key::VolumeUp),
+    (0x0E5D, Key::Apps),
+    (0xE05F, Key::Sleep),
+    // numpad keys while Num Lock is off, they are the same physical keys
+    (0xEE53, Key::KpDelete),
+    (0xEE4F, Key::Kp1),
+    (0xEE50, Key::Kp2),
+    (0xEE51, Key::Kp3),
+    (0xEE4B, Key::Kp4),
+    (0xEE4C, Key::Kp5),
+    (0xEE4D, Key::Kp6),
+    (0xEE47, Key::Kp7),
+    (0xEE48, Key::Kp8),
+    (0xEE49, Key::Kp9),
+    (0xEE52, Key::Kp0),
+];
+
 #[must_use]
-#[allow(clippy::too_many_lines)]
 pub fn key_from_code(code: u16) -> Key {
-    match code {
-        1 => Key::Escape,
-        59 => Key::F1,
-        60 => Key::F2,
-        61 => Key::F3,
-        62 => Key::F4,
-        63 => Key::F5,
-        64 => Key::F6,
-        65 => Key::F7,
-        66 => Key::F8,
-        67 => Key::F9,
-        68 => Key::F10,
-        87 => Key::F11,
-        88 => Key::F12,
-
-        41 => Key::BackQuote,
-
-        2 => Key::Num1,
-        3 => Key::Num2,
-        4 => Key::Num3,
-        5 => Key::Num4,
-        6 => Key::Num5,
-        7 => Key::Num6,
-        8 => Key::Num7,
-        9 => Key::Num8,
-        10 => Key::Num9,
-        11 => Key::Num0,
-
-        12 => Key::Minus,
-        13 | 3597 => Key::Equal,
-        14 => Key::Backspace,
-
-        15 => Key::Tab,
-        58 => Key::CapsLock,
-
-        30 => Key::KeyA,
-        48 => Key::KeyB,
-        46 => Key::KeyC,
-        32 => Key::KeyD,
-        18 => Key::KeyE,
-        33 => Key::KeyF,
-        34 => Key::KeyG,
-        35 => Key::KeyH,
-        23 => Key::KeyI,
-        36 => Key::KeyJ,
-        37 => Key::KeyK,
-        38 => Key::KeyL,
-        50 => Key::KeyM,
-        49 => Key::KeyN,
-        24 => Key::KeyO,
-        25 => Key::KeyP,
-        16 => Key::KeyQ,
-        19 => Key::KeyR,
-        31 => Key::KeyS,
-        20 => Key::KeyT,
-        22 => Key::KeyU,
-        47 => Key::KeyV,
-        17 => Key::KeyW,
-        45 => Key::KeyX,
-        21 => Key::KeyY,
-        44 => Key::KeyZ,
-
-        26 => Key::LeftBracket,
-        27 => Key::RightBracket,
-        43 => Key::BackSlash,
-
-        39 => Key::SemiColon,
-        40 => Key::Quote,
-        28 => Key::Return,
-
-        51 => Key::Comma,
-        52 | 83 => Key::Dot,
-        53 => Key::Slash,
-
-        57 => Key::Space,
-
-        3639 => Key::PrintScreen,
-        70 => Key::ScrollLock,
-        3653 => Key::Pause,
-
-        3636 | 61010 => Key::Insert,
-        3667 | 61011 => Key::Delete,
-        3655 | 60999 => Key::Home,
-        3663 | 61007 => Key::End,
-        3657 | 61001 => Key::PageUp,
-        3665 | 61009 => Key::PageDown,
-
-        57416 | 61000 => Key::UpArrow,
-        57419 | 61003 => Key::LeftArrow,
-        57421 | 61005 => Key::RightArrow,
-        57424 | 61008 => Key::DownArrow,
-
-        42 => Key::ShiftLeft,
-        54 => Key::ShiftRight,
-        29 => Key::ControlLeft,
-        3613 => Key::ControlRight,
-        56 => Key::Alt,
-        3640 => Key::AltGr,
-        3675 => Key::MetaLeft,
-        3676 => Key::MetaRight,
-
-        69 => Key::NumLock,
-        3637 => Key::KpDivide,
-        55 => Key::KpMultiply,
-        74 => Key::KpMinus,
-        78 => Key::KpPlus,
-        3612 => Key::KpReturn,
-
-        79 => Key::Kp1,
-        80 => Key::Kp2,
-        81 => Key::Kp3,
-        75 => Key::Kp4,
-        76 => Key::Kp5,
-        77 => Key::Kp6,
-        71 => Key::Kp7,
-        72 => Key::Kp8,
-        73 => Key::Kp9,
-        82 => Key::Kp0,
-
-        3666 => Key::Function,
-
-        _ => Key::Unknown(code.into()),
-    }
+    MECHVIBES_CODES
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map_or(Key::Unknown(code.into()), |(_, key)| *key)
+}
+
+/// Main Mechvibes keycode of `key`, the inverse of [`key_from_code`].
+#[must_use]
+pub fn code_from_key(key: Key) -> Option<u16> {
+    MECHVIBES_CODES
+        .iter()
+        .find(|(_, k)| *k == key)
+        .map(|(code, _)| *code)
 }
@@ -0,0 +1,220 @@
+//! Offline rendering of typing sessions, without an audio device.
+
+use anyhow::{Context, Result};
+use fastrand::Rng;
+use hound::{SampleFormat, WavSpec, WavWriter};
+use kira::Frame;
+use std::{io::BufRead, path::Path, time::Duration};
+
+use crate::{
+    audio_manager::{CUT_FADE, DEFAULT_MAX_VOICES, PlayingVoices, SoundState, Voice},
+    layout::Spatial,
+    pack::{Pack, Variation},
+    protocol::{EventReader, KeyEventKind},
+};
+
+/// A key event at a given offset from the start of the session.
+#[derive(Debug, Clone)]
+pub struct TimedKeyEvent {
+    pub time: Duration,
+    pub kind: KeyEventKind,
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderSettings {
+    /// Seed for the pitch, volume and sample variation.
+    pub seed: u64,
+    /// Volume in percent, the pack's default volume when `None`.
+    pub volume: Option<u32>,
+    pub sample_rate: u32,
+    /// Pans every key to its place on the board, like the app's spatial mode.
+    pub spatial: Option<Spatial>,
+    /// Most sounds playing at once, the oldest ones are faded out to make
+    /// room for new ones like the app does.
+    pub max_voices: usize,
+    /// Pitch and volume variation used instead of the pack's, like the one
+    /// set in the app.
+    pub variation: Option<Variation>,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            volume: None,
+            sample_rate: 48_000,
+            spatial: None,
+            max_voices: DEFAULT_MAX_VOICES,
+            variation: None,
+        }
+    }
+}
+
+/// Reads a session recorded from `key_listener`'s output, with times relative
+/// to the first event.
+pub fn read_session(reader: impl BufRead) -> Result<Vec<TimedKeyEvent>> {
+    let events = EventReader::new(reader)?.collect::<Result<Vec<_>>>()?;
+    let start = events.first().map_or(0, |event| event.timestamp_ms);
+
+    Ok(events
+        .into_iter()
+        .map(|event| TimedKeyEvent {
+            time: Duration::from_millis(event.timestamp_ms.saturating_sub(start)),
+            kind: event.kind,
+            key: event.key,
+        })
+        .collect())
+}
+
+/// Renders the sounds `pack` makes for `events` into interleaved stereo
+/// frames, picking samples and variations and cutting off sounds like the
+/// audio manager does. The same seed always gives the same frames, but they
+/// aren't kira's mix sample for sample: pitch shifts use linear
+/// interpolation.
+pub fn render(pack: Pack, events: &[TimedKeyEvent], settings: &RenderSettings) -> Vec<[f32; 2]> {
+    let mut state = SoundState::new(Rng::with_seed(settings.seed));
+    state.set_pack(pack);
+    state.set_spatial(settings.spatial);
+    state.set_variation(settings.variation);
+
+    if let Some(volume) = settings.volume {
+        state.update_volume(volume);
+    }
+
+    let mut events = events.iter().collect::<Vec<_>>();
+    events.sort_by_key(|event| event.time);
+
+    let mut voices = Vec::new();
+    // where each voice ends, and where it's cut off when it is
+    let mut ends = Vec::new();
+    let mut cuts = Vec::new();
+    let mut playing = PlayingVoices::new(settings.max_voices);
+
+    for event in events {
+        let voice = match event.kind {
+            KeyEventKind::Press => state.key_pressed(&event.key),
+            KeyEventKind::Release => state.key_released(&event.key),
+        };
+        let Some(voice) = voice else {
+            continue;
+        };
+        let start = (event.time.as_secs_f64() * f64::from(settings.sample_rate)) as usize;
+
+        playing.retain(|&index| ends[index] > start);
+        playing.make_room(voice.cut_group.as_deref(), |&mut index| {
+            cuts[index] = Some(start);
+        });
+        playing.push(voices.len(), voice.cut_group.clone());
+
+        ends.push(start + voice_length(&voice, settings.sample_rate));
+        cuts.push(None);
+        voices.push((start, voice));
+    }
+
+    let mut output = Vec::new();
+    for ((start, voice), cut) in voices.iter().zip(cuts) {
+        mix_voice(&mut output, *start, voice, cut, settings.sample_rate);
+    }
+
+    output
+}
+
+/// Renders a session like [`render`] and writes it as a 16-bit stereo WAV file.
+pub fn render_to_wav(
+    pack: Pack,
+    events: &[TimedKeyEvent],
+    settings: &RenderSettings,
+    path: &Path,
+) -> Result<()> {
+    let frames = render(pack, events, settings);
+
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: settings.sample_rate,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    };
+
+    let mut writer = WavWriter::create(path, spec)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+
+    for sample in frames.iter().flatten() {
+        let sample = (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+        writer.write_sample(sample)?;
+    }
+
+    writer
+        .finalize()
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Frames of `voice`'s sample, and how far it moves through them for each
+/// output frame.
+fn voice_frames(voice: &Voice, sample_rate: u32) -> (&[Frame], f64) {
+    let sound = &voice.sound;
+    let (first, last) = sound.slice.unwrap_or((0, sound.frames.len()));
+    let rate = 2_f64.powf(voice.semitones.0 / 12.0);
+    let step = rate * f64::from(sound.sample_rate) / f64::from(sample_rate);
+
+    (&sound.frames[first..last.min(sound.frames.len())], step)
+}
+
+/// Output frames `voice` lasts when nothing cuts it off.
+fn voice_length(voice: &Voice, sample_rate: u32) -> usize {
+    match voice_frames(voice, sample_rate) {
+        ([], _) => 0,
+        (frames, step) => ((frames.len() - 1) as f64 / step) as usize + 1,
+    }
+}
+
+/// Adds `voice` to `output` starting at frame `start`, resampling it to the
+/// output rate and its pitch shift with linear interpolation. The voice fades
+/// out from frame `cut` when given.
+fn mix_voice(
+    output: &mut Vec<[f32; 2]>,
+    start: usize,
+    voice: &Voice,
+    cut: Option<usize>,
+    sample_rate: u32,
+) {
+    let (frames, step) = voice_frames(voice, sample_rate);
+    let mut length = voice_length(voice, sample_rate);
+
+    if length == 0 {
+        return;
+    }
+
+    let amplitude = 10_f32.powf(voice.volume.0 / 20.0);
+    // constant power panning, the same law kira uses
+    let right_mix = (voice.panning.0.clamp(-1.0, 1.0) + 1.0) / 2.0;
+    let left_gain = amplitude * (1.0 - right_mix).sqrt() * std::f32::consts::SQRT_2;
+    let right_gain = amplitude * right_mix.sqrt() * std::f32::consts::SQRT_2;
+    let fade_length = (CUT_FADE.as_secs_f64() * f64::from(sample_rate)) as usize + 1;
+    if let Some(cut) = cut {
+        length = length.min(cut.saturating_sub(start) + fade_length);
+    }
+
+    if output.len() < start + length {
+        output.resize(start + length, [0.0; 2]);
+    }
+
+    for (i, out) in output[start..start + length].iter_mut().enumerate() {
+        let position = i as f64 * step;
+        let index = position as usize;
+        let fraction = (position - index as f64) as f32;
+
+        let Some(&current) = frames.get(index) else {
+            break;
+        };
+        let next = frames.get(index + 1).copied().unwrap_or(current);
+
+        let fade = match cut {
+            Some(cut) if start + i >= cut => 1.0 - (start + i - cut) as f32 / fade_length as f32,
+            _ => 1.0,
+        };
+
+        out[0] += (current.left + (next.left - current.left) * fraction) * left_gain * fade;
+        out[1] += (current.right + (next.right - current.right) * fraction) * right_gain * fade;
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    audio_manager::{AudioManager, AudioMessage, AudioState, DEFAULT_MAX_VOICES, MAX_VOICES_LIMIT},
+    layout::Spatial,
+    pack::{self, Pack, Variation},
+};
+
+/// User settings kept between launches, in `WhisperKeys/settings.json` inside
+/// the platform's config directory.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Last selected pack.
+    pub pack: Option<String>,
+    /// Volume in percent, the pack's default volume when `None`.
+    pub volume: Option<u32>,
+    pub muted: bool,
+    /// Folder the packs are installed in, `~/WhisperKeys` when `None`.
+    pub packs_dir: Option<PathBuf>,
+    /// Spatial mode settings, `None` if it was never turned on.
+    pub spatial: Option<Spatial>,
+    /// Pitch and volume variation used instead of the packs' own.
+    pub variation: Option<Variation>,
+    /// Most sounds playing at once, from 1 to [`MAX_VOICES_LIMIT`],
+    /// [`DEFAULT_MAX_VOICES`] when `None`.
+    pub max_voices: Option<usize>,
+    /// Name of the audio output device, the system's default one when `None`.
+    pub output_device: Option<String>,
+}
+
+impl Settings {
+    pub fn path() -> Result<PathBuf> {
+        let config_dir = dirs::config_dir().context("Couldn't find the user's config directory")?;
+        Ok(config_dir.join("WhisperKeys").join("settings.json"))
+    }
+
+    /// Loads the saved settings, or the defaults if none were saved yet.
+    pub fn load() -> Result<Self> {
+        Self::load_from(&Self::path()?)
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read settings from {}", path.display()))?;
+
+        let settings: Self = serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid settings file at {}", path.display()))?;
+        settings
+            .check()
+            .with_context(|| format!("Invalid settings file at {}", path.display()))?;
+
+        Ok(settings)
+    }
+
+    /// Fails on values out of their range, which would otherwise be clamped
+    /// without a word.
+    pub fn check(&self) -> Result<()> {
+        if let Some(max_voices) = self.max_voices
+            && !(1..=MAX_VOICES_LIMIT).contains(&max_voices)
+        {
+            bail!("max_voices must be between 1 and {MAX_VOICES_LIMIT}, got {max_voices}");
+        }
+
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&Self::path()?)
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+
+        let serialized =
+            serde_json::to_string_pretty(self).context("Failed to serialize settings")?;
+
+        fs::write(path, serialized)
+            .with_context(|| format!("Failed to write settings to {}", path.display()))
+    }
+
+    pub fn packs_dir(&self) -> Result<PathBuf> {
+        match &self.packs_dir {
+            Some(dir) => Ok(dir.clone()),
+            None => pack::default_packs_dir(),
+        }
+    }
+
+    /// Takes over a state changed from outside the settings, e.g. through the
+    /// control socket. Switching packs goes back to the pack's default volume.
+    pub fn update_from(&mut self, state: &AudioState) {
+        if state.pack != self.pack {
+            self.pack = state.pack.clone();
+            self.volume = None;
+        } else {
+            self.volume = Some(state.volume);
+        }
+        self.muted = state.muted;
+    }
+
+    /// Loads the saved pack into `audio_manager` and applies the saved volume,
+    /// mute state, spatial mode, variation, voice limit and output device.
+    /// Returns the volume in effect, `None` if no pack was saved.
+    pub fn restore(&self, audio_manager: &AudioManager) -> Result<Option<u32>> {
+        audio_manager.send(AudioMessage::SetMuted(self.muted))?;
+        audio_manager.send(AudioMessage::SetSpatial(self.spatial))?;
+        audio_manager.send(AudioMessage::SetVariation(self.variation))?;
+        audio_manager.send(AudioMessage::SetMaxVoices(
+            self.max_voices.unwrap_or(DEFAULT_MAX_VOICES),
+        ))?;
+        // also when `None`, the manager may be playing through another device
+        audio_manager.send(AudioMessage::SetOutputDevice(self.output_device.clone()))?;
+
+        let Some(pack_name) = &self.pack else {
+            return Ok(None);
+        };
+
+        let pack = Pack::load_from(&self.packs_dir()?, pack_name)
+            .with_context(|| format!("Failed to restore the pack '{pack_name}'"))?;
+        let volume = self.volume.unwrap_or(pack.default_volume);
+
+        audio_manager.send(AudioMessage::SetPack(pack))?;
+        audio_manager.send(AudioMessage::SetVolume(volume))?;
+
+        Ok(Some(volume))
+    }
+}
@@ -0,0 +1,352 @@
+//! Checks a pack folder for every problem at once, where loading it stops at
+//! the first one.
+
+use anyhow::Result;
+use rayon::prelude::*;
+use serde::{
+    Deserialize, Deserializer,
+    de::{IgnoredAny, MapAccess, Visitor},
+};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt, fs,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    archive,
+    keys::{self, ALL_KEYS, UNKNOWN_KEY},
+    pack::{Pack, RawPack, SoundFiles, SoundSource},
+};
+
+/// Extensions of the audio files a pack can use.
+const AUDIO_EXTENSIONS: &[&str] = &["wav", "ogg", "mp3", "flac"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The pack works, but probably not the way its author meant it to.
+    Warning,
+    /// The pack fails to load.
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+        }
+    }
+
+    fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "error: {}", self.message),
+            Severity::Warning => write!(f, "warning: {}", self.message),
+        }
+    }
+}
+
+/// Key names of the config sections in the order they are written, with
+/// duplicates kept.
+#[derive(Default, Deserialize)]
+struct KeyNames {
+    #[serde(default)]
+    keys: NameList,
+    #[serde(default)]
+    release_keys: NameList,
+    #[serde(default)]
+    pan: NameList,
+    #[serde(default)]
+    cut_previous: Vec<String>,
+}
+
+#[derive(Default)]
+struct NameList(Vec<String>);
+
+impl<'de> Deserialize<'de> for NameList {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct NameVisitor;
+
+        impl<'de> Visitor<'de> for NameVisitor {
+            type Value = NameList;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an object of keys")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<NameList, A::Error> {
+                let mut names = Vec::new();
+                while let Some((name, IgnoredAny)) = map.next_entry::<String, IgnoredAny>()? {
+                    names.push(name);
+                }
+                Ok(NameList(names))
+            }
+        }
+
+        deserializer.deserialize_map(NameVisitor)
+    }
+}
+
+/// Checks the pack in `path`, a folder or an archive, and returns every
+/// problem found, errors first. Fails only if there is no config file to check.
+pub fn validate_pack(path: &Path) -> Result<Vec<Diagnostic>> {
+    if archive::is_archive(path) && path.is_file() {
+        let (_temp, folder) = archive::unpack_to_temp(path)?;
+        return validate_pack(&folder);
+    }
+
+    let config = Pack::read_config_file(path)?;
+
+    let raw: RawPack = match json5::from_str(&config) {
+        Ok(raw) => raw,
+        Err(e) => return Ok(vec![Diagnostic::error(format!("Invalid config: {e}"))]),
+    };
+    let names: KeyNames = json5::from_str(&config).unwrap_or_default();
+
+    let mut diagnostics = Vec::new();
+
+    match raw.default_volume.parse::<u32>() {
+        Ok(1..=100) => {}
+        Ok(volume) => diagnostics.push(Diagnostic::warning(format!(
+            "default_volume is {volume}, it should be between 1 and 100"
+        ))),
+        Err(_) => diagnostics.push(Diagnostic::error(format!(
+            "default_volume must be a number between 1 and 100, got '{}'",
+            raw.default_volume
+        ))),
+    }
+
+    check_key_names("keys", &names.keys.0, &mut diagnostics);
+    check_key_names("release_keys", &names.release_keys.0, &mut diagnostics);
+    check_key_names("pan", &names.pan.0, &mut diagnostics);
+    check_key_names("cut_previous", &names.cut_previous, &mut diagnostics);
+
+    for (key, position) in raw.pan.iter().collect::<BTreeMap<_, _>>() {
+        if !(-1.0..=1.0).contains(position) {
+            diagnostics.push(Diagnostic::warning(format!(
+                "pan.{key} is {position}, it should be between -1 and 1"
+            )));
+        }
+    }
+
+    if !raw.keys.contains_key(UNKNOWN_KEY) {
+        diagnostics.push(Diagnostic::warning(format!(
+            "keys has no \"{UNKNOWN_KEY}\" entry, keys missing from the config will be silent"
+        )));
+    }
+    if raw.release_keys.contains_key(UNKNOWN_KEY) {
+        diagnostics.push(Diagnostic::warning(format!(
+            "release_keys.{UNKNOWN_KEY} is never played, use default_release for keys without \
+             a release sound"
+        )));
+    }
+
+    if let Some(variation) = raw.variation {
+        if !(0.0..=12.0).contains(&variation.pitch) {
+            diagnostics.push(Diagnostic::warning(format!(
+                "variation.pitch is {}, it should be between 0 and 12 semitones",
+                variation.pitch
+            )));
+        }
+        if !(0.0..=20.0).contains(&variation.volume) {
+            diagnostics.push(Diagnostic::warning(format!(
+                "variation.volume is {}, it should be between 0 and 20 dB",
+                variation.volume
+            )));
+        }
+    }
+
+    let sources = check_sources(&raw, &mut diagnostics);
+    check_files(path, &sources, &mut diagnostics);
+
+    diagnostics.sort_by_key(|diagnostic| std::cmp::Reverse(diagnostic.severity));
+    Ok(diagnostics)
+}
+
+fn check_key_names(section: &str, names: &[String], diagnostics: &mut Vec<Diagnostic>) {
+    let mut seen = HashSet::new();
+
+    for name in names {
+        if !seen.insert(name) {
+            diagnostics.push(Diagnostic::warning(format!(
+                "{section}.{name} is defined more than once, only the last one is used"
+            )));
+            continue;
+        }
+
+        if name == UNKNOWN_KEY || keys::key_from_name(name).is_some() {
+            continue;
+        }
+
+        let suggestion = ALL_KEYS
+            .iter()
+            .map(|key| keys::key_name(*key))
+            .chain([UNKNOWN_KEY.to_owned()])
+            .find(|key| key.eq_ignore_ascii_case(name));
+
+        let message = match suggestion {
+            Some(key) if names.contains(&key) => format!(
+                "{section}.{name} never plays, the key is named '{key}' which is also in the config"
+            ),
+            Some(key) => format!("{section}.{name} never plays, did you mean '{key}'?"),
+            None => format!("{section}.{name} doesn't match any key name and never plays"),
+        };
+        diagnostics.push(Diagnostic::warning(message));
+    }
+}
+
+/// Checks the sound lists and returns every sound source with the entry it
+/// belongs to.
+fn check_sources<'a>(
+    raw: &'a RawPack,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<(String, &'a SoundSource)> {
+    let mut sources = Vec::new();
+
+    let sections = [("keys", &raw.keys), ("release_keys", &raw.release_keys)];
+    for (section, entries) in sections {
+        // sorted so the report reads the same on every run
+        for (key, files) in entries.iter().collect::<BTreeMap<_, _>>() {
+            let entry = format!("{section}.{key}");
+
+            if files.sources().is_empty() {
+                diagnostics.push(Diagnostic::error(format!(
+                    "{entry} has an empty list of sounds"
+                )));
+            }
+
+            for source in files.sources() {
+                if source.file().is_empty() {
+                    diagnostics.push(Diagnostic::error(format!("{entry} has no sound file")));
+                } else {
+                    sources.push((entry.clone(), source));
+                }
+            }
+        }
+    }
+
+    // an empty default release means the pack has none, like in the template
+    let default_release = raw
+        .default_release
+        .as_ref()
+        .map_or(&[][..], SoundFiles::sources);
+    for source in default_release {
+        if !source.file().is_empty() {
+            sources.push((String::from("default_release"), source));
+        }
+    }
+
+    sources
+}
+
+fn check_files(path: &Path, sources: &[(String, &SoundSource)], diagnostics: &mut Vec<Diagnostic>) {
+    let mut users: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (entry, source) in sources {
+        users.entry(source.file()).or_default().push(entry);
+    }
+
+    let decoded = users
+        .par_iter()
+        .map(|(file, entries)| {
+            if !path.join(file).is_file() {
+                let message = format!("'{file}' doesn't exist, used by {}", list(entries));
+                return (*file, Err(Diagnostic::error(message)));
+            }
+
+            match Pack::load_sound(path, file) {
+                Ok(sound) => (*file, Ok(sound)),
+                Err(e) => (*file, Err(Diagnostic::error(format!("{e:#}")))),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut sounds = HashMap::new();
+    for (file, result) in decoded {
+        match result {
+            Ok(sound) => {
+                sounds.insert(file, sound);
+            }
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        }
+    }
+
+    for (entry, source) in sources {
+        if matches!(source, SoundSource::Region { .. })
+            && sounds.contains_key(source.file())
+            && let Err(e) = Pack::resolve_source(&sounds, source)
+        {
+            diagnostics.push(Diagnostic::error(format!("{entry}: {e}")));
+        }
+    }
+
+    let used = users
+        .keys()
+        .map(|file| normalize(Path::new(file)))
+        .collect::<HashSet<_>>();
+
+    let mut unused = Vec::new();
+    find_audio_files(path, Path::new(""), &mut unused);
+    unused.retain(|file| !used.contains(file));
+    unused.sort();
+
+    for file in unused {
+        diagnostics.push(Diagnostic::warning(format!(
+            "'{}' isn't used by any key",
+            file.display()
+        )));
+    }
+}
+
+/// Collects the audio files under `root`, relative to it.
+fn find_audio_files(root: &Path, relative: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(root.join(relative)) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = relative.join(entry.file_name());
+
+        if entry.path().is_dir() {
+            find_audio_files(root, &path, files);
+        } else if path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        {
+            files.push(path);
+        }
+    }
+}
+
+/// Drops `./` parts so `./a.wav` and `a.wav` compare equal.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .collect()
+}
+
+fn list(entries: &[&str]) -> String {
+    match entries {
+        [] => String::new(),
+        [only] => only.to_string(),
+        [first, second, rest @ ..] if rest.len() > 1 => {
+            format!("{first}, {second} and {} more", rest.len())
+        }
+        [rest @ .., last] => format!("{} and {last}", rest.join(", ")),
+    }
+}
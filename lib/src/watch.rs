@@ -0,0 +1,68 @@
+//! Watches pack files for changes, so edits and new packs show up without
+//! reselecting or refreshing anything.
+
+use anyhow::{Context, Result};
+use notify_debouncer_full::{
+    DebounceEventResult, Debouncer, RecommendedCache, new_debouncer,
+    notify::{RecommendedWatcher, RecursiveMode},
+};
+use std::{path::Path, time::Duration};
+
+use crate::pack::Pack;
+
+/// Editors often save a file in several steps, wait for them to settle.
+const DEBOUNCE_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// Watches files until dropped.
+pub struct Watcher {
+    _debouncer: Debouncer<RecommendedWatcher, RecommendedCache>,
+}
+
+/// Reloads the pack `pack_name` from `folder` whenever one of its files
+/// changes and hands the result to `on_reload`, from a background thread.
+pub fn watch_pack(
+    folder: &Path,
+    pack_name: &str,
+    mut on_reload: impl FnMut(Result<Pack>) + Send + 'static,
+) -> Result<Watcher> {
+    let (folder, pack_name) = (folder.to_owned(), pack_name.to_owned());
+
+    // archives are a single file, watching it recursively works the same
+    watch(&folder.join(&pack_name), move || {
+        on_reload(Pack::load_from(&folder, &pack_name))
+    })
+}
+
+/// Calls `on_change` from a background thread whenever something in the
+/// packs folder changes, e.g. a pack being added, renamed or removed.
+pub fn watch_packs_dir(
+    packs_dir: &Path,
+    on_change: impl FnMut() + Send + 'static,
+) -> Result<Watcher> {
+    // recursive, a folder only becomes a pack once its config is written
+    watch(packs_dir, on_change)
+}
+
+fn watch(path: &Path, mut on_change: impl FnMut() + Send + 'static) -> Result<Watcher> {
+    let display_path = path.display().to_string();
+
+    let handler = move |result: DebounceEventResult| match result {
+        // loading packs opens their files, which must not count as a change
+        Ok(events) if events.iter().all(|event| event.kind.is_access()) => {}
+        Ok(_) => on_change(),
+        Err(errors) => {
+            for e in errors {
+                eprintln!("Failed to watch {display_path}: {e}");
+            }
+        }
+    };
+
+    let mut debouncer = new_debouncer(DEBOUNCE_TIMEOUT, None, handler)?;
+    debouncer
+        .watch(path, RecursiveMode::Recursive)
+        .with_context(|| format!("Failed to watch {}", path.display()))?;
+
+    Ok(Watcher {
+        _debouncer: debouncer,
+    })
+}
@@ -0,0 +1,181 @@
+mod common;
+
+use common::write_wav;
+use lib::archive::{export, install};
+use lib::pack::{Pack, list_installed};
+use lib::validate::{Severity, validate_pack};
+use std::{fs, io::Write, path::Path};
+use tempfile::TempDir;
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+const CONFIG: &str = r#"{
+    creator: "Test",
+    source: "",
+    default_volume: "50",
+    keys: { KeyA: "sounds/a.wav", Unknown: "sounds/a.wav" },
+}"#;
+
+/// Contents of [`write_wav`]'s file, for putting in archives.
+fn wav(frames: usize) -> Vec<u8> {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("sound.wav");
+    write_wav(&path, frames);
+    fs::read(path).unwrap()
+}
+
+/// Zip file at `path` holding `entries`, written as they are.
+fn write_zip(path: &Path, entries: &[(&str, &[u8])]) {
+    let mut zip = ZipWriter::new(fs::File::create(path).unwrap());
+    for (name, bytes) in entries {
+        zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(bytes).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+/// Folder holding a working pack named `name`.
+fn pack_folder(name: &str) -> (TempDir, std::path::PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let folder = dir.path().join(name);
+    fs::create_dir_all(folder.join("sounds")).unwrap();
+    fs::write(folder.join("config.json5"), CONFIG).unwrap();
+    write_wav(&folder.join("sounds/a.wav"), 100);
+    (dir, folder)
+}
+
+fn entries(dir: &Path) -> Vec<String> {
+    let mut names = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+#[test]
+fn exported_packs_install_as_they_were() {
+    let (_dir, folder) = pack_folder("Clicky");
+    let out = tempfile::tempdir().unwrap();
+    let archive = out.path().join("Clicky.whisperpack");
+    let packs_dir = tempfile::tempdir().unwrap();
+
+    export(&folder, &archive).unwrap();
+    assert_eq!(install(&archive, packs_dir.path()).unwrap(), "Clicky");
+
+    let installed = packs_dir.path().join("Clicky");
+    for file in ["config.json5", "sounds/a.wav"] {
+        assert_eq!(
+            fs::read(installed.join(file)).unwrap(),
+            fs::read(folder.join(file)).unwrap(),
+            "{file}"
+        );
+    }
+    assert_eq!(list_installed(packs_dir.path()).unwrap(), ["Clicky"]);
+
+    let from_folder = Pack::load_from(packs_dir.path(), "Clicky").unwrap();
+    let from_archive = Pack::load_from(out.path(), "Clicky.whisperpack").unwrap();
+    assert_eq!(from_folder.keys["KeyA"][0].num_frames(), 100);
+    assert_eq!(from_archive.keys["KeyA"][0].num_frames(), 100);
+}
+
+#[test]
+fn archives_without_a_folder_are_named_after_the_file() {
+    let out = tempfile::tempdir().unwrap();
+    let archive = out.path().join("Flat Pack.zip");
+    write_zip(
+        &archive,
+        &[
+            ("config.json5", CONFIG.as_bytes()),
+            ("sounds/a.wav", &wav(100)),
+        ],
+    );
+    let packs_dir = tempfile::tempdir().unwrap();
+
+    assert_eq!(install(&archive, packs_dir.path()).unwrap(), "Flat Pack");
+    assert!(packs_dir.path().join("Flat Pack/sounds/a.wav").is_file());
+}
+
+#[test]
+fn entries_escaping_the_pack_are_rejected() {
+    let out = tempfile::tempdir().unwrap();
+    let packs_dir = out.path().join("packs");
+    fs::create_dir(&packs_dir).unwrap();
+    let archive = out.path().join("Evil.zip");
+    write_zip(
+        &archive,
+        &[
+            ("Evil/config.json5", CONFIG.as_bytes()),
+            ("Evil/sounds/a.wav", &wav(100)),
+            ("Evil/../../escaped.wav", &wav(100)),
+        ],
+    );
+
+    let error = install(&archive, &packs_dir).unwrap_err();
+
+    assert!(
+        format!("{error:#}").contains("outside of the archive"),
+        "{error:#}"
+    );
+    assert!(!out.path().join("escaped.wav").exists());
+    // nothing is left behind, not even the staging folder
+    assert!(entries(&packs_dir).is_empty(), "{:?}", entries(&packs_dir));
+}
+
+#[test]
+fn installed_packs_are_never_replaced() {
+    let (_dir, folder) = pack_folder("Clicky");
+    let out = tempfile::tempdir().unwrap();
+    let archive = out.path().join("Clicky.whisperpack");
+    export(&folder, &archive).unwrap();
+
+    let packs_dir = tempfile::tempdir().unwrap();
+    let existing = packs_dir.path().join("Clicky");
+    fs::create_dir(&existing).unwrap();
+    fs::write(existing.join("config.json5"), "mine").unwrap();
+
+    let error = install(&archive, packs_dir.path()).unwrap_err();
+
+    assert!(error.to_string().contains("already installed"), "{error:#}");
+    assert_eq!(
+        fs::read_to_string(existing.join("config.json5")).unwrap(),
+        "mine"
+    );
+    assert_eq!(entries(packs_dir.path()), ["Clicky"]);
+}
+
+#[test]
+fn broken_packs_are_not_installed() {
+    let out = tempfile::tempdir().unwrap();
+    let archive = out.path().join("Broken.zip");
+    write_zip(&archive, &[("Broken/config.json5", CONFIG.as_bytes())]);
+    let packs_dir = tempfile::tempdir().unwrap();
+
+    let error = install(&archive, packs_dir.path()).unwrap_err();
+
+    assert!(format!("{error:#}").contains("sounds/a.wav"), "{error:#}");
+    assert!(entries(packs_dir.path()).is_empty());
+}
+
+#[test]
+fn archives_can_be_validated() {
+    let out = tempfile::tempdir().unwrap();
+    let archive = out.path().join("Broken.whisperpack");
+    write_zip(
+        &archive,
+        &[
+            ("Broken/config.json5", CONFIG.as_bytes()),
+            ("Broken/unused.wav", &wav(100)),
+        ],
+    );
+
+    let diagnostics = validate_pack(&archive).unwrap();
+
+    assert_eq!(diagnostics.len(), 2, "{diagnostics:#?}");
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+    assert!(
+        diagnostics[0]
+            .message
+            .contains("'sounds/a.wav' doesn't exist")
+    );
+    assert!(diagnostics[1].message.contains("'unused.wav' isn't used"));
+}
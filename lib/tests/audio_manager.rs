@@ -0,0 +1,514 @@
+use kira::{
+    Frame,
+    sound::static_sound::{StaticSoundData, StaticSoundSettings},
+};
+use lib::audio_manager::{AudioManager, AudioMessage, RecordingOutput, SoundOutput, Voice};
+use lib::layout::{Layout, Spatial};
+use lib::pack::{Distribution, Pack, SampleSelection, Variation};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc,
+        mpsc::{self, Receiver},
+    },
+    time::Duration,
+};
+
+/// Silent sound whose length tells samples apart.
+fn sound(frames: usize) -> StaticSoundData {
+    StaticSoundData {
+        sample_rate: 48_000,
+        frames: Arc::from(vec![Frame::ZERO; frames]),
+        settings: StaticSoundSettings::default(),
+        slice: None,
+    }
+}
+
+fn pack(default_volume: u32, keys: &[&str]) -> Pack {
+    Pack {
+        name: String::from("Test"),
+        default_volume,
+        keys: keys
+            .iter()
+            .map(|key| (key.to_string(), vec![sound(1)]))
+            .collect(),
+        ..Pack::default()
+    }
+}
+
+fn manager(seed: u64) -> (AudioManager, Receiver<Voice>) {
+    let (output, played) = RecordingOutput::new();
+    (AudioManager::with_output(output, Some(seed)), played)
+}
+
+fn next_played(played: &Receiver<Voice>) -> Voice {
+    played
+        .recv_timeout(Duration::from_secs(5))
+        .expect("a sound should have been played")
+}
+
+fn assert_volume_near(voice: &Voice, db: f32) {
+    assert!(
+        (voice.volume.0 - db).abs() <= 1.0,
+        "volume {} should be within 1dB of {db}",
+        voice.volume.0
+    );
+}
+
+#[test]
+fn plays_the_pressed_key() {
+    let (manager, played) = manager(1);
+    manager
+        .send(AudioMessage::SetPack(pack(50, &["KeyA", "Unknown"])))
+        .unwrap();
+    manager
+        .send(AudioMessage::KeyPressed("KeyA".into()))
+        .unwrap();
+
+    assert_eq!(next_played(&played).key, "KeyA");
+}
+
+#[test]
+fn nothing_plays_without_a_pack() {
+    let (manager, played) = manager(1);
+    manager
+        .send(AudioMessage::KeyPressed("KeyA".into()))
+        .unwrap();
+    manager.send(AudioMessage::Shutdown).unwrap();
+
+    assert!(played.recv_timeout(Duration::from_secs(5)).is_err());
+}
+
+#[test]
+fn unknown_keys_fall_back_to_unknown() {
+    let (manager, played) = manager(1);
+    manager
+        .send(AudioMessage::SetPack(pack(50, &["KeyA", "Unknown"])))
+        .unwrap();
+    manager
+        .send(AudioMessage::KeyPressed("KeyZ".into()))
+        .unwrap();
+
+    assert_eq!(next_played(&played).key, "Unknown");
+}
+
+#[test]
+fn unknown_keys_are_silent_without_fallback() {
+    let (manager, played) = manager(1);
+    manager
+        .send(AudioMessage::SetPack(pack(50, &["KeyA"])))
+        .unwrap();
+    manager
+        .send(AudioMessage::KeyPressed("KeyZ".into()))
+        .unwrap();
+    manager
+        .send(AudioMessage::KeyPressed("KeyA".into()))
+        .unwrap();
+
+    assert_eq!(next_played(&played).key, "KeyA");
+}
+
+#[test]
+fn muted_keys_are_not_played() {
+    let (manager, played) = manager(1);
+    manager
+        .send(AudioMessage::SetPack(pack(50, &["KeyA", "KeyB"])))
+        .unwrap();
+    manager.send(AudioMessage::ToggleMute).unwrap();
+    manager
+        .send(AudioMessage::KeyPressed("KeyA".into()))
+        .unwrap();
+    manager
+        .send(AudioMessage::KeyReleased("KeyA".into()))
+        .unwrap();
+    manager.send(AudioMessage::ToggleMute).unwrap();
+    manager
+        .send(AudioMessage::KeyPressed("KeyB".into()))
+        .unwrap();
+
+    assert_eq!(next_played(&played).key, "KeyB");
+}
+
+#[test]
+fn volume_sets_the_level_in_decibels() {
+    let (manager, played) = manager(1);
+    manager
+        .send(AudioMessage::SetPack(pack(50, &["KeyA"])))
+        .unwrap();
+
+    manager.send(AudioMessage::SetVolume(100)).unwrap();
+    manager
+        .send(AudioMessage::KeyPressed("KeyA".into()))
+        .unwrap();
+    assert_volume_near(&next_played(&played), 0.0);
+
+    manager.send(AudioMessage::SetVolume(10)).unwrap();
+    manager
+        .send(AudioMessage::KeyPressed("KeyA".into()))
+        .unwrap();
+    assert_volume_near(&next_played(&played), -20.0);
+}
+
+#[test]
+fn switching_packs_uses_the_new_keys_and_default_volume() {
+    let (manager, played) = manager(1);
+    manager
+        .send(AudioMessage::SetPack(pack(100, &["KeyA"])))
+        .unwrap();
+    manager
+        .send(AudioMessage::KeyPressed("KeyA".into()))
+        .unwrap();
+    assert_volume_near(&next_played(&played), 0.0);
+
+    manager
+        .send(AudioMessage::SetPack(pack(10, &["KeyB"])))
+        .unwrap();
+    manager
+        .send(AudioMessage::KeyPressed("KeyA".into()))
+        .unwrap();
+    manager
+        .send(AudioMessage::KeyPressed("KeyB".into()))
+        .unwrap();
+
+    let voice = next_played(&played);
+    assert_eq!(voice.key, "KeyB");
+    assert_volume_near(&voice, -20.0);
+}
+
+#[test]
+fn variation_stays_within_range() {
+    let (manager, played) = manager(7);
+    manager
+        .send(AudioMessage::SetPack(pack(50, &["KeyA"])))
+        .unwrap();
+
+    for _ in 0..100 {
+        manager
+            .send(AudioMessage::KeyPressed("KeyA".into()))
+            .unwrap();
+        let voice = next_played(&played);
+
+        assert!(voice.semitones.0.abs() <= 0.25);
+        assert_volume_near(&voice, 20.0 * 0.5_f32.log10());
+    }
+}
+
+#[test]
+fn same_seed_gives_the_same_variation() {
+    let variations = |seed| {
+        let (manager, played) = manager(seed);
+        manager
+            .send(AudioMessage::SetPack(pack(50, &["KeyA"])))
+            .unwrap();
+
+        (0..10)
+            .map(|_| {
+                manager
+                    .send(AudioMessage::KeyPressed("KeyA".into()))
+                    .unwrap();
+                let voice = next_played(&played);
+                (voice.volume.0, voice.semitones.0)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(variations(42), variations(42));
+    assert_ne!(variations(42), variations(43));
+}
+
+#[test]
+fn round_robin_cycles_through_samples() {
+    let (manager, played) = manager(1);
+    let pack = Pack {
+        default_volume: 50,
+        keys: HashMap::from([(String::from("KeyA"), vec![sound(1), sound(2), sound(3)])]),
+        sample_selection: SampleSelection::RoundRobin,
+        ..Pack::default()
+    };
+    manager.send(AudioMessage::SetPack(pack)).unwrap();
+
+    let lengths = (0..6)
+        .map(|_| {
+            manager
+                .send(AudioMessage::KeyPressed("KeyA".into()))
+                .unwrap();
+            next_played(&played).sound.frames.len()
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(lengths, [1, 2, 3, 1, 2, 3]);
+}
+
+#[test]
+fn random_selection_never_repeats_a_sample() {
+    let (manager, played) = manager(3);
+    let pack = Pack {
+        default_volume: 50,
+        keys: HashMap::from([(String::from("KeyA"), vec![sound(1), sound(2)])]),
+        ..Pack::default()
+    };
+    manager.send(AudioMessage::SetPack(pack)).unwrap();
+
+    let mut last = None;
+    for _ in 0..20 {
+        manager
+            .send(AudioMessage::KeyPressed("KeyA".into()))
+            .unwrap();
+        let length = next_played(&played).sound.frames.len();
+
+        assert_ne!(Some(length), last);
+        last = Some(length);
+    }
+}
+
+#[test]
+fn releases_use_the_default_release_sound() {
+    let (manager, played) = manager(1);
+    let pack = Pack {
+        default_volume: 50,
+        release_keys: HashMap::from([(String::from("Space"), vec![sound(1)])]),
+        default_release: vec![sound(2)],
+        ..Pack::default()
+    };
+    manager.send(AudioMessage::SetPack(pack)).unwrap();
+
+    manager
+        .send(AudioMessage::KeyReleased("Space".into()))
+        .unwrap();
+    assert_eq!(next_played(&played).key, "Space");
+
+    manager
+        .send(AudioMessage::KeyReleased("KeyA".into()))
+        .unwrap();
+    let voice = next_played(&played);
+    assert_eq!(voice.key, "");
+    assert_eq!(voice.sound.frames.len(), 2);
+}
+
+#[test]
+fn spatial_mode_pans_keys_to_their_place() {
+    let (manager, played) = manager(1);
+    let mut pack = pack(50, &["Unknown"]);
+    pack.pan = HashMap::from([(String::from("KeyA"), 1.0)]);
+    manager.send(AudioMessage::SetPack(pack)).unwrap();
+
+    let panning = |key: &str| {
+        manager.send(AudioMessage::KeyPressed(key.into())).unwrap();
+        next_played(&played).panning.0
+    };
+
+    assert_eq!(panning("Escape"), 0.0);
+
+    manager
+        .send(AudioMessage::SetSpatial(Some(Spatial {
+            layout: Layout::Tkl,
+            width: 1.0,
+            attenuation: 0.0,
+            ..Spatial::default()
+        })))
+        .unwrap();
+
+    assert!(panning("Escape") < -0.9);
+    assert!(panning("RightArrow") > 0.9);
+    // the pack's own position wins over the layout's, and keys missing from
+    // the layout stay in the middle
+    assert_eq!(panning("KeyA"), 1.0);
+    assert_eq!(panning("Kp5"), 0.0);
+}
+
+#[test]
+fn disabled_spatial_mode_plays_keys_in_the_middle() {
+    let (manager, played) = manager(1);
+    manager
+        .send(AudioMessage::SetPack(pack(100, &["Unknown"])))
+        .unwrap();
+    manager
+        .send(AudioMessage::SetSpatial(Some(Spatial {
+            enabled: false,
+            width: 1.0,
+            attenuation: 10.0,
+            ..Spatial::default()
+        })))
+        .unwrap();
+    manager
+        .send(AudioMessage::KeyPressed("Escape".into()))
+        .unwrap();
+
+    let voice = next_played(&played);
+    assert_eq!(voice.panning.0, 0.0);
+    assert_volume_near(&voice, 0.0);
+}
+
+#[test]
+fn user_variation_overrides_the_packs() {
+    let (manager, played) = manager(5);
+    let mut pack = pack(100, &["KeyA"]);
+    pack.variation = Variation {
+        pitch: 0.0,
+        volume: 0.0,
+        ..Variation::default()
+    };
+    manager.send(AudioMessage::SetPack(pack)).unwrap();
+
+    let press = || {
+        manager
+            .send(AudioMessage::KeyPressed("KeyA".into()))
+            .unwrap();
+        next_played(&played)
+    };
+
+    let voice = press();
+    assert_eq!(voice.semitones.0, 0.0);
+    assert_eq!(voice.volume.0, 0.0);
+
+    let user = Variation {
+        pitch: 2.0,
+        volume: 6.0,
+        distribution: Distribution::Triangular,
+    };
+    manager
+        .send(AudioMessage::SetVariation(Some(user)))
+        .unwrap();
+    assert_eq!(manager.state().unwrap().variation, user);
+
+    let voices = (0..50).map(|_| press()).collect::<Vec<_>>();
+    assert!(voices.iter().all(|voice| voice.semitones.0.abs() <= 2.0));
+    assert!(voices.iter().all(|voice| voice.volume.0.abs() <= 6.0));
+    assert!(voices.iter().any(|voice| voice.semitones.0.abs() > 0.25));
+
+    manager.send(AudioMessage::SetVariation(None)).unwrap();
+    assert_eq!(press().semitones.0, 0.0);
+}
+
+#[test]
+fn listed_keys_cut_their_previous_press() {
+    let (manager, played) = manager(1);
+    let mut pack = pack(50, &["KeyA", "Space"]);
+    pack.cut_previous = HashSet::from([String::from("Space")]);
+    manager.send(AudioMessage::SetPack(pack)).unwrap();
+
+    manager
+        .send(AudioMessage::KeyPressed("KeyA".into()))
+        .unwrap();
+    assert_eq!(next_played(&played).cut_group, None);
+
+    manager
+        .send(AudioMessage::KeyPressed("Space".into()))
+        .unwrap();
+    assert_eq!(next_played(&played).cut_group.as_deref(), Some("Space"));
+}
+
+#[test]
+fn state_reports_the_output_device() {
+    let (manager, _played) = manager(1);
+    assert_eq!(manager.state().unwrap().output_device, None);
+
+    manager
+        .send(AudioMessage::SetOutputDevice(Some(String::from(
+            "Headphones",
+        ))))
+        .unwrap();
+
+    let state = manager.state().unwrap();
+    assert_eq!(state.output_device.as_deref(), Some("Headphones"));
+    assert_eq!(state.output_error, None);
+}
+
+/// Output whose device is lost at the first check and back at the next one.
+struct LostDevice {
+    checks: usize,
+}
+
+impl SoundOutput for LostDevice {
+    fn play(&mut self, _voice: Voice) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn check_device(&mut self) -> anyhow::Result<()> {
+        self.checks += 1;
+        if self.checks == 1 {
+            anyhow::bail!("device unplugged");
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn lost_output_is_reported_until_it_recovers() {
+    let manager = AudioManager::with_output(LostDevice { checks: 0 }, Some(1));
+    let (watcher, changes) = mpsc::channel();
+    manager.send(AudioMessage::WatchOutput(watcher)).unwrap();
+
+    let next_change = || changes.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(next_change(), None);
+
+    let error = next_change().expect("the failure should be reported");
+    assert!(error.contains("device unplugged"), "{error}");
+    assert_eq!(manager.state().unwrap().output_error, Some(error));
+
+    assert_eq!(next_change(), None);
+    assert_eq!(manager.state().unwrap().output_error, None);
+}
+
+/// Output that fails every sound while its device is fine.
+struct FailingPlay;
+
+impl SoundOutput for FailingPlay {
+    fn play(&mut self, _voice: Voice) -> anyhow::Result<()> {
+        anyhow::bail!("no room for another sound");
+    }
+}
+
+#[test]
+fn failed_sounds_dont_mark_the_output_lost() {
+    let manager = AudioManager::with_output(FailingPlay, Some(1));
+    manager
+        .send(AudioMessage::SetPack(pack(50, &["KeyA"])))
+        .unwrap();
+    manager
+        .send(AudioMessage::KeyPressed(String::from("KeyA")))
+        .unwrap();
+
+    assert_eq!(manager.state().unwrap().output_error, None);
+}
+
+#[test]
+fn reloads_swap_the_sounds_and_keep_the_volume() {
+    let (manager, played) = manager(1);
+    manager
+        .send(AudioMessage::SetPack(pack(50, &["KeyA"])))
+        .unwrap();
+    manager.send(AudioMessage::SetVolume(20)).unwrap();
+    manager
+        .send(AudioMessage::ReloadPack(pack(80, &["KeyB"])))
+        .unwrap();
+    manager
+        .send(AudioMessage::KeyPressed("KeyB".into()))
+        .unwrap();
+
+    assert_eq!(next_played(&played).key, "KeyB");
+    assert_eq!(manager.state().unwrap().volume, 20);
+}
+
+#[test]
+fn reloads_of_another_pack_are_ignored() {
+    let (manager, played) = manager(1);
+    let other = Pack {
+        name: String::from("Other"),
+        ..pack(50, &["KeyB"])
+    };
+    manager
+        .send(AudioMessage::SetPack(pack(50, &["KeyA"])))
+        .unwrap();
+    manager.send(AudioMessage::SetPack(other)).unwrap();
+    // the first pack changed on disk right before it was switched away from
+    manager
+        .send(AudioMessage::ReloadPack(pack(50, &["KeyA"])))
+        .unwrap();
+    manager
+        .send(AudioMessage::KeyPressed("KeyB".into()))
+        .unwrap();
+
+    assert_eq!(next_played(&played).key, "KeyB");
+    assert_eq!(manager.state().unwrap().pack.as_deref(), Some("Other"));
+}
@@ -0,0 +1,16 @@
+use std::path::Path;
+
+/// Silent mono WAV of `frames` frames at 1000 Hz, so a millisecond is a frame.
+pub fn write_wav(path: &Path, frames: usize) {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 1000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).unwrap();
+    for _ in 0..frames {
+        writer.write_sample(0i16).unwrap();
+    }
+    writer.finalize().unwrap();
+}
@@ -0,0 +1,159 @@
+#![cfg(unix)]
+
+use lib::audio_manager::{AudioManager, RecordingOutput};
+use lib::control::{self, Command, Response};
+use std::{
+    fs,
+    os::unix::{fs::PermissionsExt, net::UnixListener},
+    path::{Path, PathBuf},
+};
+
+/// Socket path in a directory `serve` creates privately.
+fn socket_in(dir: &tempfile::TempDir) -> PathBuf {
+    dir.path().join("whisperkeys").join("whisperkeys.sock")
+}
+
+/// Same as [`socket_in`], with the directory already there.
+fn existing_socket_dir(dir: &tempfile::TempDir) -> PathBuf {
+    let path = socket_in(dir);
+    let parent = path.parent().unwrap();
+    fs::create_dir(parent).unwrap();
+    fs::set_permissions(parent, fs::Permissions::from_mode(0o700)).unwrap();
+    path
+}
+
+fn serve(path: &Path) -> anyhow::Result<control::ControlSocket> {
+    let (output, _played) = RecordingOutput::new();
+    let manager = AudioManager::with_output(output, Some(1));
+    control::serve(path, manager, path.to_path_buf(), |_| {})
+}
+
+#[test]
+fn commands_round_trip_through_their_line() {
+    let commands = [
+        Command::SetPack("Cherry MX Blue".into()),
+        Command::SetVolume(1),
+        Command::SetVolume(100),
+        Command::SetMuted(true),
+        Command::SetMuted(false),
+        Command::ToggleMute,
+        Command::Status,
+    ];
+
+    for command in commands {
+        assert_eq!(Command::parse(&command.to_line()).unwrap(), command);
+    }
+}
+
+#[test]
+fn surrounding_whitespace_is_ignored() {
+    assert_eq!(
+        Command::parse("  pack   Cherry MX Blue \n").unwrap(),
+        Command::SetPack("Cherry MX Blue".into())
+    );
+    assert_eq!(Command::parse("status\r\n").unwrap(), Command::Status);
+}
+
+#[test]
+fn malformed_commands_are_rejected() {
+    for line in [
+        "",
+        "pack",
+        "pack   ",
+        "volume",
+        "volume loud",
+        "volume -5",
+        "volume 0",
+        "volume 101",
+        "mute now",
+        "toggle-mute 1",
+        "status please",
+        "PACK Cherry",
+        "jump",
+    ] {
+        assert!(Command::parse(line).is_err(), "'{line}' should be rejected");
+    }
+}
+
+#[test]
+fn serves_from_a_private_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = socket_in(&dir);
+
+    let _socket = serve(&path).unwrap();
+
+    let mode = fs::metadata(path.parent().unwrap())
+        .unwrap()
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o777, 0o700);
+    assert!(matches!(
+        control::send(&path, &Command::Status).unwrap(),
+        Response::Ok(_)
+    ));
+}
+
+#[test]
+fn refuses_a_directory_others_can_access() {
+    let dir = tempfile::tempdir().unwrap();
+    let shared = dir.path().join("shared");
+    fs::create_dir(&shared).unwrap();
+    fs::set_permissions(&shared, fs::Permissions::from_mode(0o755)).unwrap();
+
+    assert!(serve(&shared.join("whisperkeys.sock")).is_err());
+}
+
+#[test]
+fn replaces_a_stale_socket() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = existing_socket_dir(&dir);
+    drop(UnixListener::bind(&path).unwrap());
+
+    let _socket = serve(&path).unwrap();
+
+    assert!(control::send(&path, &Command::Status).is_ok());
+}
+
+#[test]
+fn leaves_other_files_in_place() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = existing_socket_dir(&dir);
+    fs::write(&path, "not a socket").unwrap();
+
+    assert!(serve(&path).is_err());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "not a socket");
+}
+
+#[test]
+fn refuses_a_second_instance() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = socket_in(&dir);
+
+    let _socket = serve(&path).unwrap();
+
+    assert!(serve(&path).is_err());
+    assert!(control::send(&path, &Command::Status).is_ok());
+}
+
+#[test]
+fn socket_is_removed_when_dropped() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = socket_in(&dir);
+
+    drop(serve(&path).unwrap());
+
+    assert!(!path.exists());
+}
+
+#[test]
+fn leaves_a_newer_socket_in_place() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = socket_in(&dir);
+    let socket = serve(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    let _other = UnixListener::bind(&path).unwrap();
+
+    drop(socket);
+
+    assert!(path.exists());
+}
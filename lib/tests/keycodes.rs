@@ -0,0 +1,232 @@
+use lib::keys::{ALL_KEYS, key_from_name, key_name};
+use lib::pack::{code_from_key, key_from_code};
+use rdev::Key;
+
+/// Main Mechvibes keycode of every key, with the name the listener sends the
+/// key as.
+const MAIN_CODES: &[(u16, &str)] = &[
+    (0x0001, "Escape"),
+    (0x003B, "F1"),
+    (0x003C, "F2"),
+    (0x003D, "F3"),
+    (0x003E, "F4"),
+    (0x003F, "F5"),
+    (0x0040, "F6"),
+    (0x0041, "F7"),
+    (0x0042, "F8"),
+    (0x0043, "F9"),
+    (0x0044, "F10"),
+    (0x0057, "F11"),
+    (0x0058, "F12"),
+    (0x005B, "F13"),
+    (0x005C, "F14"),
+    (0x005D, "F15"),
+    (0x0063, "F16"),
+    (0x0064, "F17"),
+    (0x0065, "F18"),
+    (0x0066, "F19"),
+    (0x0067, "F20"),
+    (0x0068, "F21"),
+    (0x0069, "F22"),
+    (0x006A, "F23"),
+    (0x006B, "F24"),
+    (0x0029, "BackQuote"),
+    (0x0002, "Num1"),
+    (0x0003, "Num2"),
+    (0x0004, "Num3"),
+    (0x0005, "Num4"),
+    (0x0006, "Num5"),
+    (0x0007, "Num6"),
+    (0x0008, "Num7"),
+    (0x0009, "Num8"),
+    (0x000A, "Num9"),
+    (0x000B, "Num0"),
+    (0x000C, "Minus"),
+    (0x000D, "Equal"),
+    (0x000E, "Backspace"),
+    (0x000F, "Tab"),
+    (0x003A, "CapsLock"),
+    (0x001E, "KeyA"),
+    (0x0030, "KeyB"),
+    (0x002E, "KeyC"),
+    (0x0020, "KeyD"),
+    (0x0012, "KeyE"),
+    (0x0021, "KeyF"),
+    (0x0022, "KeyG"),
+    (0x0023, "KeyH"),
+    (0x0017, "KeyI"),
+    (0x0024, "KeyJ"),
+    (0x0025, "KeyK"),
+    (0x0026, "KeyL"),
+    (0x0032, "KeyM"),
+    (0x0031, "KeyN"),
+    (0x0018, "KeyO"),
+    (0x0019, "KeyP"),
+    (0x0010, "KeyQ"),
+    (0x0013, "KeyR"),
+    (0x001F, "KeyS"),
+    (0x0014, "KeyT"),
+    (0x0016, "KeyU"),
+    (0x002F, "KeyV"),
+    (0x0011, "KeyW"),
+    (0x002D, "KeyX"),
+    (0x0015, "KeyY"),
+    (0x002C, "KeyZ"),
+    (0x001A, "LeftBracket"),
+    (0x001B, "RightBracket"),
+    (0x002B, "BackSlash"),
+    (0x0027, "SemiColon"),
+    (0x0028, "Quote"),
+    (0x001C, "Return"),
+    (0x0033, "Comma"),
+    (0x0034, "Dot"),
+    (0x0035, "Slash"),
+    (0x0039, "Space"),
+    (0x0056, "IntlBackslash"),
+    (0x002A, "ShiftLeft"),
+    (0x0036, "ShiftRight"),
+    (0x001D, "ControlLeft"),
+    (0x0E1D, "ControlRight"),
+    (0x0038, "Alt"),
+    (0x0E38, "AltGr"),
+    (0x0E5B, "MetaLeft"),
+    (0x0E5C, "MetaRight"),
+    (0x0E37, "PrintScreen"),
+    (0x0046, "ScrollLock"),
+    (0x0E45, "Pause"),
+    (0x0E52, "Insert"),
+    (0x0E53, "Delete"),
+    (0x0E47, "Home"),
+    (0x0E4F, "End"),
+    (0x0E49, "PageUp"),
+    (0x0E51, "PageDown"),
+    (0xE048, "UpArrow"),
+    (0xE04B, "LeftArrow"),
+    (0xE04D, "RightArrow"),
+    (0xE050, "DownArrow"),
+    (0x0045, "NumLock"),
+    (0x0E35, "KpDivide"),
+    (0x0037, "KpMultiply"),
+    (0x004A, "KpMinus"),
+    (0x004E, "KpPlus"),
+    (0x0E1C, "KpReturn"),
+    (0x0053, "KpDelete"),
+    (0x004F, "Kp1"),
+    (0x0050, "Kp2"),
+    (0x0051, "Kp3"),
+    (0x004B, "Kp4"),
+    (0x004C, "Kp5"),
+    (0x004D, "Kp6"),
+    (0x0047, "Kp7"),
+    (0x0048, "Kp8"),
+    (0x0049, "Kp9"),
+    (0x0052, "Kp0"),
+    (0x0E0D, "KpEqual"),
+    (0x007E, "KpComma"),
+    (0x0073, "IntlRo"),
+    (0x007D, "IntlYen"),
+    (0x0070, "KanaMode"),
+    (0xE020, "VolumeMute"),
+    (0xE02E, "VolumeDown"),
+    (0xE030, "VolumeUp"),
+    (0x0E5D, "Apps"),
+    (0xE05F, "Sleep"),
+];
+
+/// Other codes played as the same key as a main code.
+const ALIASES: &[(u16, &str)] = &[
+    (0x0E46, "IntlBackslash"),
+    (0x0E34, "Insert"),
+    (0xEE53, "KpDelete"),
+    (0xEE4F, "Kp1"),
+    (0xEE50, "Kp2"),
+    (0xEE51, "Kp3"),
+    (0xEE4B, "Kp4"),
+    (0xEE4C, "Kp5"),
+    (0xEE4D, "Kp6"),
+    (0xEE47, "Kp7"),
+    (0xEE48, "Kp8"),
+    (0xEE49, "Kp9"),
+    (0xEE52, "Kp0"),
+];
+
+#[test]
+fn main_codes_map_both_ways() {
+    for (code, name) in MAIN_CODES {
+        assert_eq!(key_name(key_from_code(*code)), *name, "code {code:#06x}");
+
+        let key = key_from_name(name).unwrap_or_else(|| panic!("{name} is not a key name"));
+        assert_eq!(code_from_key(key), Some(*code), "key {name}");
+    }
+}
+
+#[test]
+fn aliases_play_as_their_key_and_export_as_the_main_code() {
+    for (code, name) in ALIASES {
+        let key = key_from_code(*code);
+        assert_eq!(key_name(key), *name, "code {code:#06x}");
+        assert_ne!(code_from_key(key), Some(*code), "key {name}");
+    }
+}
+
+/// Named keys libuiohook has no code for.
+const KEYS_WITHOUT_CODE: &[Key] = &[
+    // keyboards handle the Fn key themselves
+    Key::Function,
+    // Windows and macOS only keys, libuiohook reads them as other keys or not
+    // at all
+    Key::KpDecimal,
+    Key::Lang1,
+    Key::Lang2,
+    Key::Lang3,
+    Key::Lang4,
+    Key::Lang5,
+    Key::Cancel,
+    Key::Clear,
+    Key::Kana,
+    Key::Hangul,
+    Key::Junja,
+    Key::Final,
+    Key::Hanja,
+    Key::Hanji,
+    Key::Print,
+    Key::Select,
+    Key::Execute,
+    Key::Help,
+    Key::Separator,
+];
+
+#[test]
+fn every_named_key_has_a_code() {
+    for key in ALL_KEYS {
+        if KEYS_WITHOUT_CODE.contains(key) {
+            assert_eq!(code_from_key(*key), None, "{key:?}");
+        } else {
+            assert!(code_from_key(*key).is_some(), "{key:?} has no code");
+        }
+    }
+}
+
+#[test]
+fn every_named_key_is_named_once() {
+    for key in ALL_KEYS {
+        assert_eq!(key_from_name(&key_name(*key)), Some(*key));
+        assert_eq!(ALL_KEYS.iter().filter(|other| *other == key).count(), 1);
+    }
+}
+
+#[test]
+fn every_code_round_trips_through_its_name() {
+    for code in 0..=u16::MAX {
+        let key = key_from_code(code);
+        if let Key::Unknown(unknown) = key {
+            assert_eq!(unknown, u32::from(code));
+            continue;
+        }
+
+        assert_eq!(key_from_name(&key_name(key)), Some(key));
+
+        let main = code_from_key(key).expect("a mapped key has a main code");
+        assert_eq!(key_from_code(main), key, "code {code:#06x}");
+    }
+}
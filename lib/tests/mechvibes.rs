@@ -0,0 +1,243 @@
+mod common;
+
+use common::write_wav;
+use lib::pack::{Pack, from_mechvibes, from_mechvibes_library, to_mechvibes};
+use std::{fs, path::PathBuf};
+use tempfile::TempDir;
+
+/// Mechvibes pack folder with `config` and the given WAV files.
+fn mechvibes_pack(config: &str, files: &[(&str, usize)]) -> TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("config.json"), config).unwrap();
+    for (file, frames) in files {
+        write_wav(&dir.path().join(file), *frames);
+    }
+    dir
+}
+
+/// WhisperKeys pack folder named `name` with `config` and the given WAV files.
+fn whisperkeys_pack(name: &str, config: &str, files: &[(&str, usize)]) -> (TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let folder = dir.path().join(name);
+    fs::create_dir(&folder).unwrap();
+    fs::write(folder.join("config.json5"), config).unwrap();
+    for (file, frames) in files {
+        write_wav(&folder.join(file), *frames);
+    }
+    (dir, folder)
+}
+
+#[test]
+fn single_pack_regions_slice_the_sprite() {
+    let source = mechvibes_pack(
+        r#"{
+            "name": "Sprite",
+            "key_define_type": "single",
+            "sound": "sprite.wav",
+            "defines": { "30": [100, 200], "31": [900, 300], "32": null }
+        }"#,
+        &[("sprite.wav", 1000)],
+    );
+    let packs_dir = tempfile::tempdir().unwrap();
+
+    let report = from_mechvibes(source.path(), packs_dir.path()).unwrap();
+    assert!(report.mismatched_codes.is_empty());
+
+    let pack = Pack::load_from(packs_dir.path(), &report.pack_name).unwrap();
+    assert_eq!(pack.keys["KeyA"][0].num_frames(), 200);
+    // the region overshoots the end of the sprite, it stops there
+    assert_eq!(pack.keys["KeyS"][0].num_frames(), 100);
+    assert!(!pack.keys.contains_key("KeyD"));
+}
+
+#[test]
+fn defines_not_fitting_the_mode_are_reported() {
+    let single = mechvibes_pack(
+        r#"{
+            "key_define_type": "single",
+            "sound": "sprite.wav",
+            "defines": { "30": [0, 100], "31": "a.wav" }
+        }"#,
+        &[("sprite.wav", 1000)],
+    );
+    let multi = mechvibes_pack(
+        r#"{ "defines": { "30": "a.wav", "31": [0, 100] } }"#,
+        &[("a.wav", 100)],
+    );
+    let packs_dir = tempfile::tempdir().unwrap();
+
+    for source in [single, multi] {
+        let report = from_mechvibes(source.path(), packs_dir.path()).unwrap();
+        assert_eq!(report.mismatched_codes, ["31"]);
+
+        let pack = Pack::load_from(packs_dir.path(), &report.pack_name).unwrap();
+        assert!(pack.keys.contains_key("KeyA"));
+        assert!(!pack.keys.contains_key("KeyS"));
+    }
+}
+
+#[test]
+fn conversion_leaves_the_source_untouched() {
+    let config = r#"{
+        "name": "Clicky: Blue",
+        "defines": { "30": "a.wav", "83": "del.wav", "61011": "del-off.wav", "9999": "u.wav" }
+    }"#;
+    let source = mechvibes_pack(
+        config,
+        &[
+            ("a.wav", 10),
+            ("del.wav", 20),
+            ("del-off.wav", 30),
+            ("u.wav", 40),
+        ],
+    );
+    let packs_dir = tempfile::tempdir().unwrap();
+
+    let report = from_mechvibes(source.path(), packs_dir.path()).unwrap();
+
+    assert_eq!(report.pack_name, "Clicky_ Blue");
+    assert_eq!(report.unmapped_codes, ["9999"]);
+    assert!(!report.missing_fallback);
+    assert_eq!(
+        fs::read_to_string(source.path().join("config.json")).unwrap(),
+        config
+    );
+    assert_eq!(fs::read_dir(source.path()).unwrap().count(), 5);
+
+    let pack = Pack::load_from(packs_dir.path(), &report.pack_name).unwrap();
+    assert_eq!(pack.keys["KeyA"][0].num_frames(), 10);
+    assert_eq!(pack.keys["Unknown"][0].num_frames(), 40);
+    // both codes of the numpad's delete key become samples of it
+    let mut delete = pack.keys["KpDelete"]
+        .iter()
+        .map(|sound| sound.num_frames())
+        .collect::<Vec<_>>();
+    delete.sort();
+    assert_eq!(delete, [20, 30]);
+}
+
+#[test]
+fn converting_twice_makes_a_second_pack() {
+    let source = mechvibes_pack(
+        r#"{ "name": "Twice", "defines": { "30": "a.wav" } }"#,
+        &[("a.wav", 10)],
+    );
+    let packs_dir = tempfile::tempdir().unwrap();
+
+    let first = from_mechvibes(source.path(), packs_dir.path()).unwrap();
+    let second = from_mechvibes(source.path(), packs_dir.path()).unwrap();
+
+    assert_eq!(first.pack_name, "Twice");
+    assert_eq!(second.pack_name, "Twice (2)");
+    assert!(first.missing_fallback);
+}
+
+#[test]
+fn library_conversion_reports_each_pack() {
+    let library = tempfile::tempdir().unwrap();
+    for (folder, config) in [
+        ("b", r#"{ "name": "B", "defines": { "30": "a.wav" } }"#),
+        ("a", r#"{ "name": "A", "defines": { "30": "a.wav" } }"#),
+        ("broken", "not json"),
+    ] {
+        let path = library.path().join(folder);
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("config.json"), config).unwrap();
+        write_wav(&path.join("a.wav"), 10);
+    }
+    fs::create_dir(library.path().join("not a pack")).unwrap();
+    let packs_dir = tempfile::tempdir().unwrap();
+
+    let results = from_mechvibes_library(library.path(), packs_dir.path()).unwrap();
+
+    let folders = results
+        .iter()
+        .map(|(folder, _)| folder.file_name().unwrap().to_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(folders, ["a", "b", "broken"]);
+    assert_eq!(results[0].1.as_ref().unwrap().pack_name, "A");
+    assert_eq!(results[1].1.as_ref().unwrap().pack_name, "B");
+    assert!(results[2].1.is_err());
+}
+
+#[test]
+fn export_reports_what_mechvibes_cant_play() {
+    let (_dir, folder) = whisperkeys_pack(
+        "Exported",
+        r#"{
+            creator: "Test",
+            source: "",
+            default_volume: "50",
+            keys: {
+                KeyA: "a.wav",
+                KeyB: { file: "sprite.wav", start: 0, duration: 10 },
+                KeyC: [],
+                KeyD: ["d1.wav", "d2.wav"],
+                Keyboard: "a.wav",
+                Unknown: "u.wav",
+            },
+            default_release: "a.wav",
+        }"#,
+        &[
+            ("a.wav", 10),
+            ("sprite.wav", 20),
+            ("d1.wav", 30),
+            ("d2.wav", 40),
+            ("u.wav", 50),
+        ],
+    );
+    let dest = tempfile::tempdir().unwrap();
+
+    let report = to_mechvibes(&folder, dest.path()).unwrap();
+
+    assert_eq!(report.region_keys, ["KeyB"]);
+    assert_eq!(report.silent_keys, ["KeyC"]);
+    assert_eq!(report.trimmed_keys, ["KeyD"]);
+    assert_eq!(report.unmapped_keys, ["Keyboard"]);
+    assert!(report.dropped_release);
+
+    let config: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(report.path.join("config.json")).unwrap())
+            .unwrap();
+    assert_eq!(config["name"], "Exported");
+    assert_eq!(config["key_define_type"], "multi");
+    let defines = &config["defines"];
+    assert_eq!(defines["30"], "a.wav");
+    assert_eq!(defines["32"], "d1.wav");
+    // left out keys play the "Unknown" sound, like they do in WhisperKeys
+    assert_eq!(defines["48"], "u.wav");
+    assert_eq!(defines["46"], "u.wav");
+
+    let mut files = fs::read_dir(&report.path)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    files.sort();
+    assert_eq!(files, ["a.wav", "config.json", "d1.wav", "u.wav"]);
+}
+
+#[test]
+fn exported_packs_convert_back() {
+    let (_dir, folder) = whisperkeys_pack(
+        "Round Trip",
+        r#"{
+            creator: "",
+            source: "",
+            default_volume: "50",
+            keys: { KeyA: "a.wav", Kp1: "kp.wav", Unknown: "u.wav" },
+        }"#,
+        &[("a.wav", 10), ("kp.wav", 20), ("u.wav", 30)],
+    );
+    let exported = tempfile::tempdir().unwrap();
+    let packs_dir = tempfile::tempdir().unwrap();
+
+    let export = to_mechvibes(&folder, exported.path()).unwrap();
+    let report = from_mechvibes(&export.path, packs_dir.path()).unwrap();
+
+    assert_eq!(report.pack_name, "Round Trip");
+    assert!(report.unmapped_codes.is_empty());
+    let pack = Pack::load_from(packs_dir.path(), &report.pack_name).unwrap();
+    assert_eq!(pack.keys["KeyA"][0].num_frames(), 10);
+    assert_eq!(pack.keys["Kp1"][0].num_frames(), 20);
+    assert_eq!(pack.keys["KeyS"][0].num_frames(), 30);
+}
@@ -0,0 +1,127 @@
+use lib::protocol::{
+    EventReader, KeyEvent, KeyEventKind, Message, ModifierTracker, Modifiers, PROTOCOL_VERSION,
+};
+use rdev::Key;
+use std::{
+    io::Cursor,
+    time::{Duration, UNIX_EPOCH},
+};
+
+fn key_event(kind: KeyEventKind, key: Key) -> KeyEvent {
+    let time = UNIX_EPOCH + Duration::from_millis(1_754_006_400_000);
+    KeyEvent::new(kind, key, 30, time, Modifiers::default())
+}
+
+fn reader(lines: &[String]) -> anyhow::Result<EventReader<Cursor<String>>> {
+    EventReader::new(Cursor::new(lines.join("\n")))
+}
+
+#[test]
+fn messages_round_trip_through_a_line() {
+    let messages = [
+        Message::hello(),
+        Message::Key(key_event(KeyEventKind::Press, Key::KeyA)),
+        Message::Key(key_event(KeyEventKind::Release, Key::Unknown(191))),
+    ];
+
+    for message in messages {
+        let line = message.to_line();
+        assert!(!line.contains('\n'), "{line}");
+        assert_eq!(Message::parse(&line).unwrap(), message);
+    }
+}
+
+#[test]
+fn parses_the_documented_lines() {
+    assert_eq!(
+        Message::parse(r#"{"type":"hello","version":1}"#).unwrap(),
+        Message::Hello { version: 1 }
+    );
+
+    let line = r#"{"type":"key","kind":"press","key":"KeyA","scan_code":30,"timestamp_ms":1754006400000,"modifiers":{"shift":true,"control":false,"alt":false,"meta":false}}"#;
+    let Message::Key(event) = Message::parse(line).unwrap() else {
+        panic!("expected a key message");
+    };
+    assert_eq!(event.kind, KeyEventKind::Press);
+    assert_eq!(event.key, "KeyA");
+    assert_eq!(event.scan_code, 30);
+    assert_eq!(event.timestamp_ms, 1_754_006_400_000);
+    assert!(event.modifiers.shift && !event.modifiers.control);
+}
+
+#[test]
+fn unknown_types_and_fields_are_ignored() {
+    assert_eq!(
+        Message::parse(r#"{"type":"mouse","x":3}"#).unwrap(),
+        Message::Unsupported
+    );
+    assert_eq!(
+        Message::parse(r#"{"type":"hello","version":1,"backend":"evdev"}"#).unwrap(),
+        Message::Hello { version: 1 }
+    );
+}
+
+#[test]
+fn malformed_lines_are_errors() {
+    for line in ["", "hello", "{\"type\":\"key\"}", "{\"version\":1}"] {
+        assert!(Message::parse(line).is_err(), "{line:?} should not parse");
+    }
+}
+
+#[test]
+fn reader_yields_key_events_after_the_handshake() {
+    let press = key_event(KeyEventKind::Press, Key::KeyA);
+    let release = key_event(KeyEventKind::Release, Key::KeyA);
+    let lines = [
+        Message::hello().to_line(),
+        Message::Key(press.clone()).to_line(),
+        String::new(),
+        r#"{"type":"mouse"}"#.to_owned(),
+        String::from("not json"),
+        Message::Key(release.clone()).to_line(),
+    ];
+
+    let events = reader(&lines).unwrap().collect::<Vec<_>>();
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[0].as_ref().unwrap(), &press);
+    assert!(events[1].is_err());
+    assert_eq!(events[2].as_ref().unwrap(), &release);
+}
+
+#[test]
+fn reader_rejects_another_protocol_version() {
+    let newer = Message::Hello {
+        version: PROTOCOL_VERSION + 1,
+    };
+
+    let e = reader(&[newer.to_line()])
+        .err()
+        .expect("a version mismatch");
+    let message = format!("{e:#}");
+    assert!(
+        message.contains(&format!("version {}", PROTOCOL_VERSION + 1)),
+        "{message}"
+    );
+}
+
+#[test]
+fn reader_needs_a_hello_first() {
+    let event = Message::Key(key_event(KeyEventKind::Press, Key::KeyA));
+
+    assert!(reader(&[event.to_line()]).is_err());
+    assert!(reader(&[]).is_err());
+}
+
+#[test]
+fn modifiers_follow_held_keys() {
+    let mut tracker = ModifierTracker::default();
+
+    tracker.update(Key::ShiftLeft, KeyEventKind::Press);
+    tracker.update(Key::ShiftRight, KeyEventKind::Press);
+    tracker.update(Key::KeyA, KeyEventKind::Press);
+    tracker.update(Key::ShiftLeft, KeyEventKind::Release);
+    assert!(tracker.modifiers().shift);
+
+    tracker.update(Key::ShiftRight, KeyEventKind::Release);
+    assert_eq!(tracker.modifiers(), Modifiers::default());
+}
@@ -0,0 +1,123 @@
+use kira::{
+    Frame,
+    sound::static_sound::{StaticSoundData, StaticSoundSettings},
+};
+use lib::pack::{Pack, Variation};
+use lib::protocol::KeyEventKind;
+use lib::render::{RenderSettings, TimedKeyEvent, render};
+use std::{sync::Arc, time::Duration};
+
+/// Pack whose keys play 480 frames of a constant level at 48 kHz.
+fn pack(variation: Variation) -> Pack {
+    let sound = StaticSoundData {
+        sample_rate: 48_000,
+        frames: Arc::from(vec![Frame::from_mono(0.5); 480]),
+        settings: StaticSoundSettings::default(),
+        slice: None,
+    };
+
+    Pack {
+        name: String::from("Test"),
+        default_volume: 50,
+        keys: [(String::from("KeyA"), vec![sound])].into(),
+        variation,
+        ..Pack::default()
+    }
+}
+
+fn presses(times_ms: &[u64]) -> Vec<TimedKeyEvent> {
+    times_ms
+        .iter()
+        .map(|ms| TimedKeyEvent {
+            time: Duration::from_millis(*ms),
+            kind: KeyEventKind::Press,
+            key: String::from("KeyA"),
+        })
+        .collect()
+}
+
+#[test]
+fn sounds_start_at_their_event() {
+    let still = Variation {
+        pitch: 0.0,
+        volume: 0.0,
+        ..Variation::default()
+    };
+    let settings = RenderSettings {
+        seed: 7,
+        ..RenderSettings::default()
+    };
+
+    let frames = render(pack(still), &presses(&[0, 100]), &settings);
+
+    // the second press starts 100ms in, and both play their 480 frames
+    assert_eq!(frames.len(), 4800 + 480);
+    assert!(frames[..480].iter().all(|frame| frame[0] > 0.0));
+    assert!(frames[480..4800].iter().all(|frame| *frame == [0.0; 2]));
+    assert_eq!(frames[..480], frames[4800..]);
+    // centered, so both channels are the same
+    assert!(frames.iter().all(|frame| frame[0] == frame[1]));
+}
+
+#[test]
+fn same_seed_renders_the_same_frames() {
+    let events = presses(&[0, 30, 75, 120]);
+    let seeded = |seed| RenderSettings {
+        seed,
+        ..RenderSettings::default()
+    };
+
+    let first = render(pack(Variation::default()), &events, &seeded(42));
+    let again = render(pack(Variation::default()), &events, &seeded(42));
+    let other = render(pack(Variation::default()), &events, &seeded(43));
+
+    assert_eq!(first, again);
+    assert_ne!(first, other);
+}
+
+#[test]
+fn voice_limit_fades_out_the_oldest() {
+    let still = Variation {
+        pitch: 0.0,
+        volume: 0.0,
+        ..Variation::default()
+    };
+    let limited = |max_voices| RenderSettings {
+        max_voices,
+        ..RenderSettings::default()
+    };
+    // the second press starts halfway through the first sound
+    let events = presses(&[0, 5]);
+
+    let one = render(pack(still), &events, &limited(1));
+    let two = render(pack(still), &events, &limited(2));
+
+    assert_eq!(one.len(), two.len());
+    assert_eq!(one[..241], two[..241]);
+    assert!(
+        one[241..480]
+            .iter()
+            .zip(&two[241..480])
+            .all(|(one, two)| one[0] < two[0])
+    );
+    assert_eq!(one[480..], two[480..]);
+}
+
+#[test]
+fn user_variation_replaces_the_packs() {
+    let still = Variation {
+        pitch: 0.0,
+        volume: 0.0,
+        ..Variation::default()
+    };
+    let events = presses(&[0, 30, 75]);
+    let overridden = RenderSettings {
+        variation: Some(still),
+        ..RenderSettings::default()
+    };
+
+    assert_eq!(
+        render(pack(Variation::default()), &events, &overridden),
+        render(pack(still), &events, &RenderSettings::default())
+    );
+}
@@ -0,0 +1,77 @@
+use lib::audio_manager::{AudioManager, AudioMessage, MAX_VOICES_LIMIT, RecordingOutput};
+use lib::settings::Settings;
+use std::fs;
+
+fn load(contents: &str) -> anyhow::Result<Settings> {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("settings.json");
+    fs::write(&path, contents).unwrap();
+    Settings::load_from(&path)
+}
+
+#[test]
+fn missing_file_loads_the_defaults() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let settings = Settings::load_from(&dir.path().join("settings.json")).unwrap();
+
+    assert_eq!(settings.max_voices, None);
+    assert_eq!(settings.pack, None);
+}
+
+#[test]
+fn saved_settings_load_back() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("nested").join("settings.json");
+    let settings = Settings {
+        pack: Some(String::from("Clicky")),
+        volume: Some(40),
+        max_voices: Some(MAX_VOICES_LIMIT),
+        ..Settings::default()
+    };
+
+    settings.save_to(&path).unwrap();
+    let loaded = Settings::load_from(&path).unwrap();
+
+    assert_eq!(loaded.pack.as_deref(), Some("Clicky"));
+    assert_eq!(loaded.volume, Some(40));
+    assert_eq!(loaded.max_voices, Some(MAX_VOICES_LIMIT));
+}
+
+#[test]
+fn voice_limits_in_range_load() {
+    for max_voices in [1, 32, MAX_VOICES_LIMIT] {
+        let settings = load(&format!(r#"{{ "max_voices": {max_voices} }}"#)).unwrap();
+        assert_eq!(settings.max_voices, Some(max_voices));
+    }
+}
+
+#[test]
+fn voice_limits_out_of_range_are_rejected() {
+    for max_voices in [0, MAX_VOICES_LIMIT + 1, 100_000] {
+        let error = load(&format!(r#"{{ "max_voices": {max_voices} }}"#)).unwrap_err();
+        let message = format!("{error:#}");
+        assert!(
+            message.contains(&format!(
+                "max_voices must be between 1 and {MAX_VOICES_LIMIT}, got {max_voices}"
+            )),
+            "{message}"
+        );
+    }
+}
+
+#[test]
+fn restoring_goes_back_to_the_default_device() {
+    let (output, _played) = RecordingOutput::new();
+    let manager = AudioManager::with_output(output, Some(1));
+    manager
+        .send(AudioMessage::SetOutputDevice(Some(String::from(
+            "Headphones",
+        ))))
+        .unwrap();
+
+    let volume = Settings::default().restore(&manager).unwrap();
+
+    assert_eq!(volume, None);
+    assert_eq!(manager.state().unwrap().output_device, None);
+}
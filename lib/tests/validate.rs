@@ -0,0 +1,141 @@
+mod common;
+
+use common::write_wav;
+use lib::validate::{Diagnostic, Severity, validate_pack};
+use std::fs;
+use tempfile::TempDir;
+
+/// Pack folder whose config has `keys` and `extra` fields, with a 1 second
+/// `a.wav` in it.
+fn pack(keys: &str, extra: &str) -> TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let config = format!(
+        r#"{{
+            creator: "Test",
+            source: "",
+            default_volume: "50",
+            keys: {{ {keys} }},
+            {extra}
+        }}"#
+    );
+    fs::write(dir.path().join("config.json5"), config).unwrap();
+    write_wav(&dir.path().join("a.wav"), 1000);
+    dir
+}
+
+fn validate(pack: &TempDir) -> Vec<Diagnostic> {
+    validate_pack(pack.path()).unwrap()
+}
+
+/// Asserts one diagnostic of `severity` mentions every part of `message`.
+fn assert_reported(diagnostics: &[Diagnostic], severity: Severity, message: &[&str]) {
+    assert!(
+        diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == severity
+                && message.iter().all(|part| diagnostic.message.contains(part))),
+        "no {severity:?} mentioning {message:?} in {diagnostics:#?}"
+    );
+}
+
+#[test]
+fn a_correct_pack_has_nothing_to_report() {
+    let pack = pack(r#"KeyA: "a.wav", Unknown: "a.wav""#, "");
+
+    assert_eq!(validate(&pack), vec![]);
+}
+
+#[test]
+fn missing_default_is_a_warning() {
+    let pack = pack(r#"KeyA: "a.wav""#, "");
+
+    let diagnostics = validate(&pack);
+    assert_eq!(diagnostics.len(), 1);
+    assert_reported(&diagnostics, Severity::Warning, &["\"Unknown\""]);
+}
+
+#[test]
+fn missing_file_is_an_error() {
+    let pack = pack(
+        r#"KeyA: "a.wav", KeyB: "missing.wav", KeyC: "missing.wav", Unknown: "a.wav""#,
+        "",
+    );
+
+    assert_reported(
+        &validate(&pack),
+        Severity::Error,
+        &["'missing.wav' doesn't exist", "keys.KeyB and keys.KeyC"],
+    );
+}
+
+#[test]
+fn missing_config_fails() {
+    let dir = tempfile::tempdir().unwrap();
+
+    assert!(validate_pack(dir.path()).is_err());
+}
+
+#[test]
+fn unknown_key_names_are_warnings() {
+    let pack = pack(
+        r#"keya: "a.wav", Keyboard: "a.wav", Unknown: "a.wav""#,
+        r#"pan: { KeyQ: 0.5, Kp11: -0.5 },"#,
+    );
+
+    let diagnostics = validate(&pack);
+    assert_reported(
+        &diagnostics,
+        Severity::Warning,
+        &["keys.keya", "did you mean 'KeyA'?"],
+    );
+    assert_reported(
+        &diagnostics,
+        Severity::Warning,
+        &["keys.Keyboard", "doesn't match any key name"],
+    );
+    assert_reported(
+        &diagnostics,
+        Severity::Warning,
+        &["pan.Kp11", "doesn't match any key name"],
+    );
+    assert!(diagnostics.iter().all(|d| !d.message.contains("KeyQ")));
+}
+
+#[test]
+fn regions_outside_the_file_are_errors() {
+    let pack = pack(
+        r#"
+            KeyA: { file: "a.wav", start: 500, duration: 200 },
+            KeyB: { file: "a.wav", start: 1200, duration: 100 },
+            KeyC: { file: "a.wav", start: 100, duration: 0 },
+            Unknown: "a.wav",
+        "#,
+        "",
+    );
+
+    let diagnostics = validate(&pack);
+    assert_eq!(diagnostics.len(), 2, "{diagnostics:#?}");
+    assert_reported(&diagnostics, Severity::Error, &["keys.KeyB", "1200ms"]);
+    assert_reported(&diagnostics, Severity::Error, &["keys.KeyC", "for 0ms"]);
+}
+
+#[test]
+fn out_of_range_variation_is_a_warning() {
+    let pack = pack(
+        r#"KeyA: "a.wav", Unknown: "a.wav""#,
+        "variation: { pitch: 13, volume: -1 },",
+    );
+
+    let diagnostics = validate(&pack);
+    assert_reported(&diagnostics, Severity::Warning, &["variation.pitch is 13"]);
+    assert_reported(&diagnostics, Severity::Warning, &["variation.volume is -1"]);
+}
+
+#[test]
+fn errors_come_before_warnings() {
+    let pack = pack(r#"KeyA: "missing.wav""#, "variation: { pitch: 13 },");
+
+    let severities: Vec<_> = validate(&pack).iter().map(|d| d.severity).collect();
+    assert!(severities.is_sorted_by(|a, b| a >= b), "{severities:?}");
+    assert_eq!(severities.first(), Some(&Severity::Error));
+}
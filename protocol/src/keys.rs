@@ -0,0 +1,190 @@
+//! Key names used in pack configs. They are the `Debug` names of
+//! [`rdev::Key`], which is also what `key_listener` sends.
+
+use rdev::Key;
+
+/// Defines [`ALL_KEYS`] from the names given, along with a match over
+/// `rdev::Key` that breaks the build if the list and the enum ever disagree.
+macro_rules! named_keys {
+    ($($key:ident),* $(,)?) => {
+        /// Every named key, in the order of `rdev::Key`. Keys without a name are
+        /// sent as `Unknown(<code>)`.
+        pub const ALL_KEYS: &[Key] = &[$(Key::$key),*];
+
+        #[allow(dead_code)]
+        fn is_named(key: Key) -> bool {
+            match key {
+                $(Key::$key)|* => true,
+                Key::Unknown(_) | Key::RawKey(_) => false,
+            }
+        }
+    };
+}
+
+named_keys! {
+    Alt,
+    AltGr,
+    Backspace,
+    CapsLock,
+    ControlLeft,
+    ControlRight,
+    Delete,
+    DownArrow,
+    End,
+    Escape,
+    F1,
+    F10,
+    F11,
+    F12,
+    F13,
+    F14,
+    F15,
+    F16,
+    F17,
+    F18,
+    F19,
+    F2,
+    F20,
+    F21,
+    F22,
+    F23,
+    F24,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    Home,
+    LeftArrow,
+    MetaLeft,
+    MetaRight,
+    PageDown,
+    PageUp,
+    Return,
+    RightArrow,
+    ShiftLeft,
+    ShiftRight,
+    Space,
+    Tab,
+    UpArrow,
+    PrintScreen,
+    ScrollLock,
+    Pause,
+    NumLock,
+    BackQuote,
+    Num1,
+    Num2,
+    Num3,
+    Num4,
+    Num5,
+    Num6,
+    Num7,
+    Num8,
+    Num9,
+    Num0,
+    Minus,
+    Equal,
+    KeyQ,
+    KeyW,
+    KeyE,
+    KeyR,
+    KeyT,
+    KeyY,
+    KeyU,
+    KeyI,
+    KeyO,
+    KeyP,
+    LeftBracket,
+    RightBracket,
+    KeyA,
+    KeyS,
+    KeyD,
+    KeyF,
+    KeyG,
+    KeyH,
+    KeyJ,
+    KeyK,
+    KeyL,
+    SemiColon,
+    Quote,
+    BackSlash,
+    IntlBackslash,
+    IntlRo,
+    IntlYen,
+    KanaMode,
+    KeyZ,
+    KeyX,
+    KeyC,
+    KeyV,
+    KeyB,
+    KeyN,
+    KeyM,
+    Comma,
+    Dot,
+    Slash,
+    Insert,
+    KpReturn,
+    KpMinus,
+    KpPlus,
+    KpMultiply,
+    KpDivide,
+    KpDecimal,
+    KpEqual,
+    KpComma,
+    Kp0,
+    Kp1,
+    Kp2,
+    Kp3,
+    Kp4,
+    Kp5,
+    Kp6,
+    Kp7,
+    Kp8,
+    Kp9,
+    KpDelete,
+    Function,
+    VolumeUp,
+    VolumeDown,
+    VolumeMute,
+    Lang1,
+    Lang2,
+    Lang3,
+    Lang4,
+    Lang5,
+    Apps,
+    Cancel,
+    Clear,
+    Kana,
+    Hangul,
+    Junja,
+    Final,
+    Hanja,
+    Hanji,
+    Print,
+    Select,
+    Execute,
+    Help,
+    Sleep,
+    Separator,
+}
+
+/// Name of the pack entry played for keys that have no entry of their own.
+pub const UNKNOWN_KEY: &str = "Unknown";
+
+pub fn key_name(key: Key) -> String {
+    format!("{key:?}")
+}
+
+/// The key a config name refers to, including `Unknown(<code>)` names.
+pub fn key_from_name(name: &str) -> Option<Key> {
+    if let Some(code) = name
+        .strip_prefix("Unknown(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        return code.parse().ok().map(Key::Unknown);
+    }
+
+    ALL_KEYS.iter().copied().find(|key| key_name(*key) == name)
+}
@@ -0,0 +1,224 @@
+//! Line-delimited JSON protocol spoken by `key_listener` on its stdout.
+//!
+//! The first line is always a [`Message::Hello`] carrying the protocol version,
+//! every following line is a [`Message::Key`]:
+//!
+//! ```text
+//! {"type":"hello","version":1}
+//! {"type":"key","kind":"press","key":"KeyA","scan_code":30,"timestamp_ms":1754006400000,"modifiers":{"shift":true,"control":false,"alt":false,"meta":false}}
+//! {"type":"key","kind":"release","key":"KeyA","scan_code":30,"timestamp_ms":1754006400084,"modifiers":{"shift":true,"control":false,"alt":false,"meta":false}}
+//! ```
+//!
+//! `key` is the `Debug` name of the `rdev::Key`, the same name packs use in
+//! their config. `scan_code` is whatever the listening backend reports for the
+//! physical key and is platform specific. `timestamp_ms` is the time of the
+//! event in milliseconds since the Unix epoch.
+//!
+//! Readers ignore fields and message types they don't know about, so both can
+//! be added without bumping [`PROTOCOL_VERSION`]. Changing the meaning of an
+//! existing field requires a new version.
+
+use anyhow::{Context, Result, bail};
+use rdev::Key;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::BufRead,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub mod keys;
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    Hello {
+        version: u32,
+    },
+    Key(KeyEvent),
+    /// Message types added by newer listeners.
+    #[serde(other)]
+    Unsupported,
+}
+
+impl Message {
+    pub fn hello() -> Self {
+        Message::Hello {
+            version: PROTOCOL_VERSION,
+        }
+    }
+
+    /// Serializes the message as a single line, without the trailing newline.
+    pub fn to_line(&self) -> String {
+        serde_json::to_string(self).expect("protocol messages always serialize")
+    }
+
+    pub fn parse(line: &str) -> Result<Self> {
+        serde_json::from_str(line.trim())
+            .with_context(|| format!("Invalid message from key_listener: '{}'", line.trim()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyEvent {
+    pub kind: KeyEventKind,
+    pub key: String,
+    pub scan_code: u32,
+    pub timestamp_ms: u64,
+    pub modifiers: Modifiers,
+}
+
+impl KeyEvent {
+    pub fn new(
+        kind: KeyEventKind,
+        key: Key,
+        scan_code: u32,
+        time: SystemTime,
+        modifiers: Modifiers,
+    ) -> Self {
+        let timestamp_ms = time
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_millis() as u64);
+
+        Self {
+            kind,
+            key: keys::key_name(key),
+            scan_code,
+            timestamp_ms,
+            modifiers,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyEventKind {
+    Press,
+    Release,
+}
+
+/// Modifier state at the time of the event, left and right keys are merged.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+    pub meta: bool,
+}
+
+/// Tracks which modifier keys are held down from the stream of key events.
+#[derive(Debug, Default)]
+pub struct ModifierTracker {
+    held: Vec<Key>,
+}
+
+impl ModifierTracker {
+    pub fn update(&mut self, key: Key, kind: KeyEventKind) {
+        if Self::modifier_of(key).is_none() {
+            return;
+        }
+
+        match kind {
+            KeyEventKind::Press if !self.held.contains(&key) => self.held.push(key),
+            KeyEventKind::Press => {}
+            KeyEventKind::Release => self.held.retain(|held| *held != key),
+        }
+    }
+
+    pub fn modifiers(&self) -> Modifiers {
+        let mut modifiers = Modifiers::default();
+
+        for key in &self.held {
+            match Self::modifier_of(*key) {
+                Some(Modifier::Shift) => modifiers.shift = true,
+                Some(Modifier::Control) => modifiers.control = true,
+                Some(Modifier::Alt) => modifiers.alt = true,
+                Some(Modifier::Meta) => modifiers.meta = true,
+                None => {}
+            }
+        }
+
+        modifiers
+    }
+
+    fn modifier_of(key: Key) -> Option<Modifier> {
+        match key {
+            Key::ShiftLeft | Key::ShiftRight => Some(Modifier::Shift),
+            Key::ControlLeft | Key::ControlRight => Some(Modifier::Control),
+            Key::Alt | Key::AltGr => Some(Modifier::Alt),
+            Key::MetaLeft | Key::MetaRight => Some(Modifier::Meta),
+            _ => None,
+        }
+    }
+}
+
+enum Modifier {
+    Shift,
+    Control,
+    Alt,
+    Meta,
+}
+
+/// Reads key events from a listener's output, after checking its handshake.
+pub struct EventReader<R> {
+    lines: std::io::Lines<R>,
+    broken: bool,
+}
+
+impl<R: BufRead> EventReader<R> {
+    /// Waits for the listener's hello message and checks its version.
+    pub fn new(reader: R) -> Result<Self> {
+        let mut lines = reader.lines();
+
+        let line = lines
+            .next()
+            .context("key_listener exited before sending its hello message")?
+            .context("Failed to read from key_listener")?;
+
+        match Message::parse(&line)? {
+            Message::Hello { version } if version == PROTOCOL_VERSION => Ok(Self {
+                lines,
+                broken: false,
+            }),
+            Message::Hello { version } => bail!(
+                "key_listener speaks protocol version {version}, \
+                but version {PROTOCOL_VERSION} is expected"
+            ),
+            _ => bail!("key_listener didn't start with a hello message"),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for EventReader<R> {
+    type Item = Result<KeyEvent>;
+
+    /// Yields every key event, or an error for lines that can't be parsed.
+    /// Ends when the listener's output closes, after a final error if it
+    /// couldn't be read anymore.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.broken {
+            return None;
+        }
+
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => {
+                    self.broken = true;
+                    return Some(Err(e).context("Pipe from key_listener broke"));
+                }
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match Message::parse(&line) {
+                Ok(Message::Key(event)) => return Some(Ok(event)),
+                Ok(Message::Hello { .. } | Message::Unsupported) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! Serves the control socket while the window is open and reports the
+//! changes it makes so the window can follow them.
+
+use iced::Subscription;
+use lib::audio_manager::AudioManager;
+use std::path::PathBuf;
+
+use crate::Message;
+
+#[cfg(unix)]
+pub fn subscription(audio_manager: AudioManager, packs_dir: PathBuf) -> Subscription<Message> {
+    use iced::futures::{SinkExt, StreamExt, channel::mpsc};
+    use lib::control;
+
+    let stream = iced::stream::channel(10, move |mut output| async move {
+        let (sender, mut changes) = mpsc::unbounded();
+        let served = control::serve(
+            &control::socket_path(),
+            audio_manager,
+            packs_dir,
+            move |state| {
+                let _ = sender.unbounded_send(state.clone());
+            },
+        );
+
+        // removes the socket when the subscription goes away
+        let _socket = match served {
+            Ok(socket) => socket,
+            Err(e) => {
+                let _ = output.send(Message::ControlFailed(format!("{e:#}"))).await;
+                return;
+            }
+        };
+
+        while let Some(state) = changes.next().await {
+            let _ = output.send(Message::RemoteChanged(state)).await;
+        }
+    });
+
+    Subscription::run_with_id("control-socket", stream)
+}
+
+#[cfg(not(unix))]
+pub fn subscription(_audio_manager: AudioManager, _packs_dir: PathBuf) -> Subscription<Message> {
+    Subscription::none()
+}
@@ -0,0 +1,118 @@
+//! Runs WhisperKeys without a window, e.g. as a systemd user service.
+//!
+//! The pack, volume and mute state come from the saved settings. SIGHUP
+//! reloads them, except for `packs_dir` which only changes on a restart,
+//! SIGUSR1 toggles mute and SIGINT or SIGTERM stop the daemon. Changes made
+//! through the control socket are saved to the settings too.
+
+use anyhow::Result;
+
+#[cfg(unix)]
+pub fn run() -> Result<()> {
+    use anyhow::{Context, bail};
+    use lib::audio_manager::{AudioManager, AudioMessage};
+    use lib::control;
+    use signal_hook::{
+        consts::{SIGHUP, SIGINT, SIGTERM, SIGUSR1},
+        iterator::Signals,
+    };
+
+    let audio_manager = AudioManager::new().context("Failed to create audio manager")?;
+    let packs_dir = apply_settings(&audio_manager, None).packs_dir()?;
+
+    let _socket = control::serve(
+        &control::socket_path(),
+        audio_manager.clone(),
+        packs_dir.clone(),
+        save_state,
+    )?;
+
+    let mut signals = Signals::new([SIGHUP, SIGUSR1, SIGINT, SIGTERM])
+        .context("Failed to register signal handlers")?;
+
+    // stop waiting for signals if the helper dies, there is nothing left to do
+    let handle = signals.handle();
+    let mut child = crate::listener::spawn(audio_manager.clone(), move || handle.close())?;
+
+    for signal in signals.forever() {
+        match signal {
+            SIGHUP => {
+                // the control socket keeps loading packs from the first one
+                apply_settings(&audio_manager, Some(&packs_dir));
+            }
+            SIGUSR1 => {
+                audio_manager.send(AudioMessage::ToggleMute)?;
+                save_state(&audio_manager.state()?);
+            }
+            _ => break,
+        }
+    }
+
+    // the helper's output closed, wait for it to exit to report how
+    let exit_status = if signals.is_closed() {
+        Some(child.wait()?)
+    } else {
+        child.try_wait()?
+    };
+
+    if let Err(e) = child.kill() {
+        eprintln!("Failed to kill key_listener: {e}");
+    }
+    audio_manager.shutdown();
+
+    if let Some(status) = exit_status {
+        bail!("key_listener exited unexpectedly ({status})");
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn run() -> Result<()> {
+    anyhow::bail!("Headless mode is only supported on Unix systems")
+}
+
+/// Loads the saved settings and applies them, reporting problems without
+/// stopping so they can be fixed and reloaded. The pack is loaded from
+/// `packs_dir` when given instead of the saved one.
+#[cfg(unix)]
+fn apply_settings(
+    audio_manager: &lib::audio_manager::AudioManager,
+    packs_dir: Option<&std::path::Path>,
+) -> lib::settings::Settings {
+    use lib::settings::Settings;
+
+    let mut settings = Settings::load().unwrap_or_else(|e| {
+        eprintln!("Failed to load settings: {e:#}");
+        Settings::default()
+    });
+    if let Some(packs_dir) = packs_dir {
+        settings.packs_dir = Some(packs_dir.to_path_buf());
+    }
+
+    match settings.restore(audio_manager) {
+        Ok(Some(_)) => {}
+        Ok(None) => eprintln!(
+            "No pack selected yet, choose one in the UI or set \"pack\" in the settings file"
+        ),
+        Err(e) => eprintln!("{e:#}"),
+    }
+
+    settings
+}
+
+/// Saves a state changed while running, keeping the rest of the settings as
+/// they are on disk.
+#[cfg(unix)]
+fn save_state(state: &lib::audio_manager::AudioState) {
+    use lib::settings::Settings;
+
+    let result = Settings::load().and_then(|mut settings| {
+        settings.update_from(state);
+        settings.save()
+    });
+
+    if let Err(e) = result {
+        eprintln!("Failed to save settings: {e:#}");
+    }
+}
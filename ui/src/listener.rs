@@ -0,0 +1,134 @@
+use anyhow::{Context, Result};
+use lib::audio_manager::{AudioManager, AudioMessage};
+use lib::protocol::{EventReader, KeyEventKind};
+use std::path::PathBuf;
+use std::{
+    io::BufReader,
+    process::{Child, Command, Stdio},
+    thread,
+};
+
+/// Overrides every other location of the key_listener helper when set.
+const HELPER_ENV_VAR: &str = "WHISPERKEYS_HELPER";
+const HELPER_NAME: &str = "key_listener";
+
+fn helper_path() -> Result<PathBuf> {
+    if let Some(path) = std::env::var_os(HELPER_ENV_VAR) {
+        let path = PathBuf::from(path);
+        anyhow::ensure!(
+            path.is_file(),
+            "{HELPER_ENV_VAR} is set to '{}', but no file exists there",
+            path.display()
+        );
+        return Ok(path);
+    }
+
+    let self_path =
+        std::env::current_exe().context("Couldn't locate the WhisperKeys executable")?;
+    let exe_dir = self_path
+        .parent()
+        .context("The WhisperKeys executable has no parent directory")?;
+
+    let candidates = if cfg!(debug_assertions) {
+        vec![exe_dir.join("../release").join(HELPER_NAME)]
+    } else if cfg!(target_os = "macos") {
+        vec![exe_dir.join("../Resources").join(HELPER_NAME)]
+    } else {
+        // Next to the executable for plain archives, then the package layout
+        // (/usr/bin/whisperkeys-ui with /usr/lib/whisperkeys/key_listener).
+        vec![
+            exe_dir.join(HELPER_NAME),
+            exe_dir.join("../lib/whisperkeys").join(HELPER_NAME),
+        ]
+    };
+
+    let in_path = std::env::var_os("PATH")
+        .map(|paths| {
+            std::env::split_paths(&paths)
+                .map(|dir| dir.join(HELPER_NAME))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    candidates
+        .iter()
+        .chain(&in_path)
+        .find(|path| path.is_file())
+        .cloned()
+        .with_context(|| {
+            format!(
+                "Couldn't find the {HELPER_NAME} helper (looked in {} and $PATH), \
+                set {HELPER_ENV_VAR} to its location",
+                candidates
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+}
+
+/// Launches the key_listener helper and forwards its key events to
+/// `audio_manager` from a background thread, which calls `on_exit` once the
+/// helper's output ends.
+pub fn spawn(
+    audio_manager: AudioManager,
+    on_exit: impl FnOnce() + Send + 'static,
+) -> Result<Child> {
+    let helper_path = helper_path()?;
+
+    let mut command = Command::new(&helper_path);
+
+    // rdev's global hook can't see keys under Wayland, read the devices instead
+    if cfg!(target_os = "linux") && std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        command.args(["--backend", "evdev"]);
+    }
+
+    let mut child = command
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to launch helper at {}", helper_path.display()))?;
+
+    let stdout = child
+        .stdout
+        .take()
+        .context("Failed to get stdout from child process")?;
+    let reader = BufReader::new(stdout);
+
+    thread::spawn(move || {
+        forward_events(reader, &audio_manager);
+        on_exit();
+    });
+
+    Ok(child)
+}
+
+fn forward_events(reader: BufReader<std::process::ChildStdout>, audio_manager: &AudioManager) {
+    let events = match EventReader::new(reader) {
+        Ok(events) => events,
+        Err(e) => {
+            eprintln!("Failed to connect to key_listener: {e:#}");
+            return;
+        }
+    };
+
+    for event in events {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                eprintln!("{e:#}");
+                continue;
+            }
+        };
+
+        let msg = match event.kind {
+            KeyEventKind::Press => AudioMessage::KeyPressed(event.key),
+            KeyEventKind::Release => AudioMessage::KeyReleased(event.key),
+        };
+
+        if let Err(e) = audio_manager.send(msg) {
+            eprintln!("Failed to send key message: {}", e);
+            break;
+        }
+    }
+}
@@ -1,81 +1,52 @@
 use anyhow::{Context, Result};
 use iced::daemon::Appearance;
 use iced::widget::{Column, Space, button, column, container, pick_list, row, slider, text};
-use iced::{Alignment, Color, Element, Length, Padding, Size, Task};
-use lib::audio_manager::{AudioManager, AudioMessage};
-use lib::pack::Pack;
+use iced::{Alignment, Color, Element, Length, Padding, Size, Subscription, Task};
+use lib::audio_manager::{AudioManager, AudioMessage, AudioState};
+use lib::layout::{Layout, Spatial};
+use lib::pack::{ConversionReport, Distribution, Pack, Variation};
+use lib::settings::Settings;
 use std::path::PathBuf;
-use std::{
-    io::{BufRead, BufReader},
-    process::{Command, Stdio},
-    thread,
-};
 
+mod control;
+mod headless;
+mod listener;
 mod style;
-
-fn helper_path() -> PathBuf {
-    let self_path = std::env::current_exe().unwrap();
-
-    if cfg!(debug_assertions) {
-        return self_path
-            .parent()
-            .unwrap()
-            .parent()
-            .unwrap()
-            .join("release/key_listener");
-    }
-
-    if cfg!(target_os = "macos") {
-        self_path
-            .parent()
-            .unwrap()
-            .parent()
-            .unwrap()
-            .join("Resources")
-            .join("key_listener")
-    } else {
-        panic!(
-            "Unsupported platform, only macOS is supported for now, please open an issue on GitHub if you want to see support for other platforms"
-        );
-    }
-}
+mod watch;
 
 fn main() -> Result<()> {
-    let helper_path = helper_path();
-
-    let mut child = Command::new(helper_path)
-        .stdout(Stdio::piped())
-        .spawn()
-        .expect("Failed to launch helper");
-
-    let stdout = child
-        .stdout
-        .take()
-        .context("Failed to get stdout from child process")?;
-    let reader = BufReader::new(stdout);
+    if std::env::args().skip(1).any(|arg| arg == "--headless") {
+        return headless::run();
+    }
 
     let audio_manager = AudioManager::new().context("Failed to create audio manager")?;
 
-    let home = std::env::home_dir().context("Couldn't get the user's home directory")?;
-    let packs_dir = home.join("WhisperKeys");
-
-    let am = audio_manager.clone();
-    thread::spawn(move || {
-        for line in reader.lines() {
-            match line {
-                Ok(key) => {
-                    if let Err(e) = am.send(AudioMessage::KeyPressed(key.trim().to_string())) {
-                        eprintln!("Failed to send key press message: {}", e);
-                        break;
-                    }
-                }
-                Err(err) => {
-                    eprintln!("Pipe from key_listener broke: {err}");
-                    break;
-                }
-            }
+    let (settings, settings_error) = match Settings::load() {
+        Ok(settings) => (settings, None),
+        Err(e) => {
+            eprintln!("Failed to load settings: {e:#}");
+            (Settings::default(), Some(format!("{e:#}")))
         }
-    });
+    };
+    let packs_dir = settings.packs_dir()?;
+
+    let (volume, mut error_msg) = match settings.restore(&audio_manager) {
+        Ok(volume) => (volume, None),
+        Err(e) => (None, Some(format!("{e:#}"))),
+    };
+    if let Some(e) = &settings_error {
+        error_msg = Some(format!("{e}, changes won't be saved until it's fixed"));
+    }
+    let selected_pack = volume.and(settings.pack.clone());
+
+    let state = audio_manager.state().ok();
+    let variation = state
+        .as_ref()
+        .map(|state| state.variation)
+        .unwrap_or_default();
+    let output_error = state.and_then(|state| state.output_error);
+
+    let mut child = listener::spawn(audio_manager.clone(), || {})?;
 
     let installed_packs = lib::pack::list_installed(&packs_dir).unwrap_or_else(|e| {
         eprintln!("Failed to load initial packs: {}", e);
@@ -86,7 +57,8 @@ fn main() -> Result<()> {
     iced::application("", WhisperKeys::update, WhisperKeys::view)
         .level(iced::window::Level::AlwaysOnTop)
         .resizable(false)
-        .window_size(Size::new(400.0, 600.0))
+        .window_size(Size::new(400.0, 750.0))
+        .subscription(WhisperKeys::subscription)
         .style(|_, _| Appearance {
             background_color: *style::BACKGROUND_COLOR,
             text_color: Color::WHITE,
@@ -95,18 +67,25 @@ fn main() -> Result<()> {
             (
                 WhisperKeys {
                     audio_manager,
-                    error_msg: None,
+                    error_msg,
                     installed_packs,
-                    selected_pack: None,
+                    selected_pack,
                     packs_path: packs_dir,
-                    volume: None,
-                    muted: false,
+                    volume,
+                    muted: settings.muted,
+                    variation,
+                    output_devices: list_output_devices(),
+                    output_error,
+                    settings,
+                    settings_error,
                 },
                 Task::none(),
             )
         })?;
 
-    child.kill().expect("Failed to kill key_listener");
+    if let Err(e) = child.kill() {
+        eprintln!("Failed to kill key_listener: {e}");
+    }
 
     Ok(())
 }
@@ -115,11 +94,28 @@ fn main() -> Result<()> {
 enum Message {
     PackSelected(String),
     PackListRefreshed,
+    PacksChanged,
     VolumeChanged(u32),
+    /// A slider was let go, saves the value it was dragged to.
+    SliderReleased,
     TranslatePack,
     OpenConfigsPath,
     ToggleMute,
+    ToggleSpatial,
+    LayoutSelected(Layout),
+    PitchVariationChanged(f64),
+    VolumeVariationChanged(f32),
+    DistributionSelected(Distribution),
+    ResetVariation,
+    OutputDevicesRequested,
+    OutputDeviceSelected(String),
+    OutputChanged(Option<String>),
     CreateNewPack,
+    InstallPack,
+    ExportPack,
+    PackReloaded(Result<(), String>),
+    RemoteChanged(AudioState),
+    ControlFailed(String),
 }
 
 struct WhisperKeys {
@@ -130,6 +126,16 @@ struct WhisperKeys {
     error_msg: Option<String>,
     volume: Option<u32>,
     muted: bool,
+    /// Variation in effect, the user's or the pack's.
+    variation: Variation,
+    /// Output devices to choose from, the system's default first.
+    output_devices: Vec<String>,
+    /// Why the sounds don't play through the chosen device, if they don't.
+    output_error: Option<String>,
+    settings: Settings,
+    /// Why the settings file failed to load. It is never saved over then, so
+    /// the user's own edits aren't lost.
+    settings_error: Option<String>,
 }
 
 impl WhisperKeys {
@@ -142,30 +148,58 @@ impl WhisperKeys {
                 if let Err(e) = self.audio_manager.send(AudioMessage::SetVolume(v)) {
                     self.error_msg = Some(format!("Failed to set volume: {}", e));
                 }
+
+                self.settings.volume = Some(v);
             }
+            SliderReleased => self.save_settings(),
             PackSelected(p) => {
                 self.error_msg = None;
                 match Pack::load_from(&self.packs_path, &p) {
                     Ok(pack) => {
-                        self.selected_pack = Some(p);
+                        self.selected_pack = Some(p.clone());
                         self.volume = Some(pack.default_volume);
+                        self.variation = self.settings.variation.unwrap_or(pack.variation);
                         if let Err(e) = self.audio_manager.send(AudioMessage::SetPack(pack)) {
                             self.error_msg = Some(format!("Failed to set pack: {}", e));
                         }
+
+                        self.settings.pack = Some(p);
+                        self.settings.volume = None;
+                        self.save_settings();
                     }
                     Err(e) => self.error_msg = Some(e.to_string()),
                 }
             }
             PackListRefreshed => {
                 self.error_msg = None;
-                let packs = lib::pack::list_installed(&self.packs_path).unwrap_or_default();
-                self.installed_packs = format_pack_list(packs);
+                self.refresh_pack_list();
             }
+            PacksChanged => self.refresh_pack_list(),
             TranslatePack => {
                 self.error_msg = None;
-                if let Some(folder) = rfd::FileDialog::new().pick_folder() {
-                    if let Err(e) = lib::pack::from_mechvibes(&folder) {
-                        self.error_msg = Some(format!("Translation failed: {}", e));
+                let Some(folder) = rfd::FileDialog::new().pick_folder() else {
+                    return;
+                };
+
+                // a folder without a config is taken as a library of packs
+                if folder.join("config.json").exists() {
+                    match lib::pack::from_mechvibes(&folder, &self.packs_path) {
+                        Ok(report) => {
+                            self.refresh_pack_list();
+                            show_conversion_report(&report);
+                        }
+                        Err(e) => self.error_msg = Some(format!("Translation failed: {e:#}")),
+                    }
+                } else {
+                    match lib::pack::from_mechvibes_library(&folder, &self.packs_path) {
+                        Ok(results) if results.is_empty() => {
+                            self.error_msg = Some(String::from("No Mechvibes packs found there"));
+                        }
+                        Ok(results) => {
+                            self.refresh_pack_list();
+                            show_library_report(&results);
+                        }
+                        Err(e) => self.error_msg = Some(format!("Translation failed: {e:#}")),
                     }
                 }
             }
@@ -179,12 +213,82 @@ impl WhisperKeys {
                 if let Err(e) = self.audio_manager.send(AudioMessage::ToggleMute) {
                     self.error_msg = Some(format!("Failed to toggle mute: {}", e));
                 }
+
+                self.settings.muted = self.muted;
+                self.save_settings();
+            }
+            ToggleSpatial => {
+                let spatial = match self.settings.spatial {
+                    Some(spatial) => Spatial {
+                        enabled: !spatial.enabled,
+                        ..spatial
+                    },
+                    None => Spatial::default(),
+                };
+                self.set_spatial(Some(spatial));
+            }
+            LayoutSelected(layout) => {
+                let spatial = Spatial {
+                    layout,
+                    ..self.settings.spatial.unwrap_or_default()
+                };
+                self.set_spatial(Some(spatial));
+            }
+            PitchVariationChanged(pitch) => {
+                self.set_variation(Some(Variation {
+                    pitch,
+                    ..self.variation
+                }));
+            }
+            VolumeVariationChanged(volume) => {
+                self.set_variation(Some(Variation {
+                    volume,
+                    ..self.variation
+                }));
+            }
+            DistributionSelected(distribution) => {
+                self.set_variation(Some(Variation {
+                    distribution,
+                    ..self.variation
+                }));
+                self.save_settings();
+            }
+            ResetVariation => {
+                self.set_variation(None);
+                self.save_settings();
+            }
+            OutputDevicesRequested => self.output_devices = list_output_devices(),
+            OutputDeviceSelected(name) => {
+                let device = (name != SYSTEM_DEFAULT_DEVICE).then_some(name);
+                if let Err(e) = self
+                    .audio_manager
+                    .send(AudioMessage::SetOutputDevice(device.clone()))
+                {
+                    self.error_msg = Some(format!("Failed to set the output device: {}", e));
+                }
+
+                // falling back to the default device is reported in the state
+                self.output_error = self
+                    .audio_manager
+                    .state()
+                    .ok()
+                    .and_then(|state| state.output_error);
+
+                self.settings.output_device = device;
+                self.save_settings();
+            }
+            OutputChanged(error) => {
+                self.output_error = error;
+                self.output_devices = list_output_devices();
             }
             CreateNewPack => {
                 self.error_msg = None;
                 if let Some(folder) = rfd::FileDialog::new().pick_folder() {
-                    if let Err(e) = lib::pack::create_new_pack(&folder) {
-                        self.error_msg = Some(format!("Failed to create new pack: {}", e));
+                    match lib::pack::create_new_pack(&folder) {
+                        Ok(_) => self.refresh_pack_list(),
+                        Err(e) => {
+                            self.error_msg = Some(format!("Failed to create new pack: {}", e))
+                        }
                     }
 
                     if let Err(e) = open::that(folder) {
@@ -192,6 +296,115 @@ impl WhisperKeys {
                     }
                 }
             }
+            InstallPack => {
+                self.error_msg = None;
+                let archive = rfd::FileDialog::new()
+                    .add_filter("Pack archive", lib::archive::ARCHIVE_EXTENSIONS)
+                    .pick_file();
+
+                if let Some(archive) = archive {
+                    match lib::archive::install(&archive, &self.packs_path) {
+                        Ok(_) => self.refresh_pack_list(),
+                        Err(e) => self.error_msg = Some(format!("{e:#}")),
+                    }
+                }
+            }
+            ExportPack => {
+                self.error_msg = None;
+                let Some(pack) = &self.selected_pack else {
+                    return;
+                };
+
+                let archive = rfd::FileDialog::new()
+                    .add_filter("WhisperKeys pack", &["whisperpack"])
+                    .set_file_name(format!("{pack}.whisperpack"))
+                    .save_file();
+
+                if let Some(archive) = archive
+                    && let Err(e) = lib::archive::export(&self.packs_path.join(pack), &archive)
+                {
+                    self.error_msg = Some(format!("Export failed: {e:#}"));
+                }
+            }
+            PackReloaded(result) => {
+                self.error_msg = result.err().map(|e| format!("Failed to reload pack: {e}"));
+
+                // the pack's variation may have changed with it
+                if let Ok(state) = self.audio_manager.state() {
+                    self.variation = state.variation;
+                }
+            }
+            RemoteChanged(state) => {
+                self.selected_pack = state.pack.clone();
+                self.volume = state.pack.is_some().then_some(state.volume);
+                self.muted = state.muted;
+                self.variation = state.variation;
+
+                self.settings.update_from(&state);
+                self.save_settings();
+            }
+            ControlFailed(e) => {
+                self.error_msg = Some(format!("Control socket unavailable: {e}"));
+            }
+        }
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        Subscription::batch([
+            control::subscription(self.audio_manager.clone(), self.packs_path.clone()),
+            watch::selected_pack(
+                self.audio_manager.clone(),
+                self.packs_path.clone(),
+                self.selected_pack.clone(),
+            ),
+            watch::packs_dir(self.packs_path.clone()),
+            watch::output(self.audio_manager.clone()),
+        ])
+    }
+
+    fn refresh_pack_list(&mut self) {
+        let packs = lib::pack::list_installed(&self.packs_path).unwrap_or_default();
+        self.installed_packs = format_pack_list(packs);
+    }
+
+    fn set_spatial(&mut self, spatial: Option<Spatial>) {
+        if let Err(e) = self.audio_manager.send(AudioMessage::SetSpatial(spatial)) {
+            self.error_msg = Some(format!("Failed to set the spatial mode: {}", e));
+        }
+
+        self.settings.spatial = spatial;
+        self.save_settings();
+    }
+
+    /// Applies `variation` without saving it, so sliders can save once let go.
+    fn set_variation(&mut self, variation: Option<Variation>) {
+        if let Err(e) = self
+            .audio_manager
+            .send(AudioMessage::SetVariation(variation))
+        {
+            self.error_msg = Some(format!("Failed to set the variation: {}", e));
+        }
+
+        self.variation = match variation {
+            Some(variation) => variation,
+            None => self
+                .audio_manager
+                .state()
+                .map(|state| state.variation)
+                .unwrap_or_default(),
+        };
+
+        self.settings.variation = variation;
+    }
+
+    fn save_settings(&mut self) {
+        if let Some(e) = &self.settings_error {
+            self.error_msg = Some(format!("Settings not saved, {e}"));
+            return;
+        }
+
+        if let Err(e) = self.settings.save() {
+            self.error_msg = Some(format!("Failed to save settings: {e:#}"));
         }
     }
 
@@ -204,6 +417,12 @@ impl WhisperKeys {
                 .push(self.pack_selection())
                 .push_maybe((self.volume.is_some()).then_some(Space::with_height(15)))
                 .push_maybe(self.volume_control())
+                .push_maybe((self.volume.is_some()).then_some(Space::with_height(15)))
+                .push_maybe(self.variation_control())
+                .push(Space::with_height(15))
+                .push(self.spatial_control())
+                .push(Space::with_height(15))
+                .push(self.output_device_selection())
                 .push(Space::with_height(15))
                 .push(self.utils_buttons()),
         )
@@ -260,6 +479,7 @@ impl WhisperKeys {
 
         let volume_text = text(format!("{}%", volume));
         let mut slider = slider(1..=100, volume, Message::VolumeChanged)
+            .on_release(Message::SliderReleased)
             .style(style::volume_slider())
             .step(10u32);
 
@@ -290,6 +510,106 @@ impl WhisperKeys {
         )
     }
 
+    fn variation_control(&self) -> Option<Element<'_, Message>> {
+        self.volume?;
+        let variation = self.variation;
+
+        let pitch = row![
+            text(format!("Pitch ±{:.2} st", variation.pitch)).width(Length::Fixed(120.0)),
+            slider(0.0..=2.0, variation.pitch, Message::PitchVariationChanged)
+                .on_release(Message::SliderReleased)
+                .step(0.05)
+                .style(style::volume_slider()),
+        ]
+        .align_y(Alignment::Center);
+
+        let volume = row![
+            text(format!("Volume ±{:.1} dB", variation.volume)).width(Length::Fixed(120.0)),
+            slider(0.0..=6.0, variation.volume, Message::VolumeVariationChanged)
+                .on_release(Message::SliderReleased)
+                .step(0.5)
+                .style(style::volume_slider()),
+        ]
+        .align_y(Alignment::Center);
+
+        let distribution = pick_list(
+            Distribution::ALL,
+            Some(variation.distribution),
+            Message::DistributionSelected,
+        )
+        .padding(Padding::default().right(10).left(10).top(5).bottom(5))
+        .style(style::picklist());
+
+        let reset = button("Use the pack's")
+            .on_press_maybe(
+                self.settings
+                    .variation
+                    .is_some()
+                    .then_some(Message::ResetVariation),
+            )
+            .style(style::generic_button());
+
+        Some(
+            column![
+                pitch,
+                volume,
+                row![distribution, Space::with_width(10), reset].align_y(Alignment::Center)
+            ]
+            .spacing(8)
+            .into(),
+        )
+    }
+
+    fn spatial_control(&self) -> Element<'_, Message> {
+        let spatial = self.settings.spatial.filter(|spatial| spatial.enabled);
+
+        let toggle = button(if spatial.is_some() {
+            "Spatial sound: on"
+        } else {
+            "Spatial sound: off"
+        })
+        .on_press(Message::ToggleSpatial)
+        .style(style::generic_button());
+
+        let layout = spatial.map(|spatial| {
+            pick_list(Layout::ALL, Some(spatial.layout), Message::LayoutSelected)
+                .padding(Padding::default().right(10).left(10).top(5).bottom(5))
+                .style(style::picklist())
+        });
+
+        row![toggle, Space::with_width(10)]
+            .push_maybe(layout)
+            .align_y(Alignment::Center)
+            .into()
+    }
+
+    fn output_device_selection(&self) -> Element<'_, Message> {
+        let selected = self
+            .settings
+            .output_device
+            .clone()
+            .unwrap_or_else(|| SYSTEM_DEFAULT_DEVICE.to_owned());
+
+        let pick_list = pick_list(
+            self.output_devices.clone(),
+            Some(selected),
+            Message::OutputDeviceSelected,
+        )
+        .on_open(Message::OutputDevicesRequested)
+        .padding(Padding::default().right(10).left(10).top(5).bottom(5))
+        .style(style::picklist());
+
+        let device_row =
+            row![text("Output"), Space::with_width(10), pick_list].align_y(Alignment::Center);
+
+        let error = self
+            .output_error
+            .as_ref()
+            .map(|error| text(error.as_str()).color(style::ERROR_COLOR));
+
+        column![device_row].push_maybe(error).spacing(5).into()
+    }
+
     fn utils_buttons(&self) -> Element<'_, Message> {
         let from_mechvibes = button(text("Convert mechvibes config").align_x(Alignment::Center))
             .on_press(Message::TranslatePack)
@@ -306,12 +626,103 @@ impl WhisperKeys {
             .width(Length::Fixed(200.0))
             .style(style::generic_button());
 
-        column![from_mechvibes, open_folder, create_pack]
-            .spacing(6)
-            .width(Length::Fill)
-            .align_x(Alignment::Center)
-            .into()
+        let install_pack = button(text("Install pack archive").align_x(Alignment::Center))
+            .on_press(Message::InstallPack)
+            .width(Length::Fixed(200.0))
+            .style(style::generic_button());
+
+        // archives are already shareable as they are
+        let exportable = self
+            .selected_pack
+            .as_ref()
+            .is_some_and(|pack| self.packs_path.join(pack).is_dir());
+        let export_pack = button(text("Export selected pack").align_x(Alignment::Center))
+            .on_press_maybe(exportable.then_some(Message::ExportPack))
+            .width(Length::Fixed(200.0))
+            .style(style::generic_button());
+
+        column![
+            from_mechvibes,
+            open_folder,
+            create_pack,
+            install_pack,
+            export_pack
+        ]
+        .spacing(6)
+        .width(Length::Fill)
+        .align_x(Alignment::Center)
+        .into()
+    }
+}
+
+fn show_conversion_report(report: &ConversionReport) {
+    let mut description = format!("Converted to the pack '{}'.", report.pack_name);
+
+    if !report.unmapped_codes.is_empty() {
+        description.push_str(&format!(
+            "\n\nThese keycodes don't match any key and play as unknown keys: {}",
+            report.unmapped_codes.join(", ")
+        ));
+    }
+    if !report.mismatched_codes.is_empty() {
+        description.push_str(&format!(
+            "\n\nThese keycodes have a sound that doesn't fit the pack's key_define_type and \
+             were left out: {}",
+            report.mismatched_codes.join(", ")
+        ));
+    }
+    if report.missing_fallback {
+        description.push_str("\n\nThe pack has no sound for unknown keys, they will be silent.");
     }
+
+    rfd::MessageDialog::new()
+        .set_title("Mechvibes pack converted")
+        .set_description(description)
+        .set_level(rfd::MessageLevel::Info)
+        .show();
+}
+
+fn show_library_report(results: &[(PathBuf, Result<ConversionReport>)]) {
+    let converted = results.iter().filter(|(_, result)| result.is_ok()).count();
+    let mut description = format!("Converted {converted} of {} packs.", results.len());
+
+    let failures = results
+        .iter()
+        .filter_map(|(source, result)| {
+            let e = result.as_ref().err()?;
+            let name = source.file_name()?.to_string_lossy();
+            Some(format!("{name}: {e:#}"))
+        })
+        .collect::<Vec<_>>();
+
+    if !failures.is_empty() {
+        description.push_str("\n\nFailed:\n");
+        description.push_str(&failures.join("\n"));
+    }
+
+    rfd::MessageDialog::new()
+        .set_title("Mechvibes packs converted")
+        .set_description(description)
+        .set_level(if failures.is_empty() {
+            rfd::MessageLevel::Info
+        } else {
+            rfd::MessageLevel::Warning
+        })
+        .show();
+}
+
+/// Entry of the output device list for the system's default device.
+const SYSTEM_DEFAULT_DEVICE: &str = "System default";
+
+fn list_output_devices() -> Vec<String> {
+    let devices = lib::audio_manager::output_devices().unwrap_or_else(|e| {
+        eprintln!("{e:#}");
+        Vec::new()
+    });
+
+    std::iter::once(SYSTEM_DEFAULT_DEVICE.to_owned())
+        .chain(devices)
+        .collect()
 }
 
 fn format_pack_list(packs: Vec<String>) -> Vec<String> {
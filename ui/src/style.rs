@@ -87,6 +87,12 @@ pub fn generic_button() -> impl Fn(&Theme, button::Status) -> button::Style {
             border: Border::default().rounded(6),
             shadow: Shadow::default(),
         },
+        button::Status::Disabled => button::Style {
+            background: Some(Background::Color(*DARKER_BACKGROUND_COLOR)),
+            text_color: Color::from_rgb8(0x88, 0x88, 0x88),
+            border: Border::default().rounded(6),
+            shadow: Shadow::default(),
+        },
         _ => button::Style {
             background: Some(Background::Color(*LIGHT_PRIMARY_COLOR)),
             text_color: Color::WHITE,
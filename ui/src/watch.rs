@@ -0,0 +1,100 @@
+//! Follows changes to the pack files: reloads the selected pack when its files
+//! change and rescans the packs folder when packs come and go. Also follows the
+//! audio output as devices are lost and found again.
+
+use iced::Subscription;
+use iced::futures::{SinkExt, StreamExt, channel::mpsc};
+use lib::audio_manager::{AudioManager, AudioMessage};
+use std::{path::PathBuf, sync::mpsc as std_mpsc, thread};
+
+use crate::Message;
+
+pub fn selected_pack(
+    audio_manager: AudioManager,
+    packs_dir: PathBuf,
+    pack: Option<String>,
+) -> Subscription<Message> {
+    let Some(pack) = pack else {
+        return Subscription::none();
+    };
+
+    // a new pack gets a new id, which drops the previous watcher
+    let id = ("pack-watch", packs_dir.clone(), pack.clone());
+
+    let stream = iced::stream::channel(10, move |mut output| async move {
+        let (sender, mut reloads) = mpsc::unbounded();
+        let watcher = lib::watch::watch_pack(&packs_dir, &pack, move |result| {
+            let result = result.and_then(|pack| audio_manager.send(AudioMessage::ReloadPack(pack)));
+            let _ = sender.unbounded_send(result.map_err(|e| format!("{e:#}")));
+        });
+
+        let _watcher = match watcher {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                let _ = output
+                    .send(Message::PackReloaded(Err(format!("{e:#}"))))
+                    .await;
+                return;
+            }
+        };
+
+        while let Some(result) = reloads.next().await {
+            let _ = output.send(Message::PackReloaded(result)).await;
+        }
+    });
+
+    Subscription::run_with_id(id, stream)
+}
+
+pub fn packs_dir(packs_dir: PathBuf) -> Subscription<Message> {
+    let id = ("packs-dir-watch", packs_dir.clone());
+
+    let stream = iced::stream::channel(10, move |mut output| async move {
+        let (sender, mut changes) = mpsc::unbounded();
+        let watcher = lib::watch::watch_packs_dir(&packs_dir, move || {
+            let _ = sender.unbounded_send(());
+        });
+
+        // the folder may not exist yet, the Refresh button still works then
+        let _watcher = match watcher {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                eprintln!("{e:#}");
+                return;
+            }
+        };
+
+        while changes.next().await.is_some() {
+            let _ = output.send(Message::PacksChanged).await;
+        }
+    });
+
+    Subscription::run_with_id(id, stream)
+}
+
+pub fn output(audio_manager: AudioManager) -> Subscription<Message> {
+    let stream = iced::stream::channel(10, move |mut output| async move {
+        let (watcher, errors) = std_mpsc::channel();
+        if let Err(e) = audio_manager.send(AudioMessage::WatchOutput(watcher)) {
+            eprintln!("{e:#}");
+            return;
+        }
+
+        // the audio thread reports on a blocking channel, forwarded from a
+        // thread of its own
+        let (sender, mut changes) = mpsc::unbounded();
+        thread::spawn(move || {
+            while let Ok(error) = errors.recv() {
+                if sender.unbounded_send(error).is_err() {
+                    break;
+                }
+            }
+        });
+
+        while let Some(error) = changes.next().await {
+            let _ = output.send(Message::OutputChanged(error)).await;
+        }
+    });
+
+    Subscription::run_with_id("output-watch", stream)
+}